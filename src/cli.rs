@@ -0,0 +1,76 @@
+//! Command line options. Only useful on native, the web build never gets any args.
+
+use std::error::Error;
+
+use crate::net_sim::NetworkSimulation;
+
+#[derive(Debug, Default)]
+pub struct Cli {
+    /// Run as a headless observer of this room instead of as a player
+    pub observe: Option<String>,
+    /// Number of players in the observed room
+    pub players: Option<usize>,
+    /// Matchbox server to use instead of the default one
+    pub server: Option<String>,
+    /// Address the observer serves match state on
+    pub http: Option<String>,
+    /// How far behind live the observer serves match state, in seconds
+    pub delay_secs: Option<f32>,
+    /// Skip matchmaking and run a local SyncTest session with this many players, re-simulating
+    /// every frame and reporting any checksum mismatch
+    pub synctest: Option<usize>,
+    /// Run without a window or renderer
+    pub headless: bool,
+    /// Artificial delay added to every GGRS message (debug builds only)
+    pub sim_latency_ms: Option<u32>,
+    /// Random extra delay on top of the latency (debug builds only)
+    pub sim_jitter_ms: Option<u32>,
+    /// Chance from 0 to 1 of dropping each GGRS message (debug builds only)
+    pub sim_packet_loss: Option<f32>,
+}
+
+impl Cli {
+    pub fn parse() -> Result<Cli, Box<dyn Error>> {
+        let mut cli = Cli::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {arg}"))
+            };
+
+            match arg.as_str() {
+                "--observe" => cli.observe = Some(value()?),
+                "--players" => cli.players = Some(value()?.parse()?),
+                "--server" => cli.server = Some(value()?),
+                "--http" => cli.http = Some(value()?),
+                "--delay-secs" => cli.delay_secs = Some(value()?.parse()?),
+                "--synctest" => cli.synctest = Some(value()?.parse()?),
+                "--headless" => cli.headless = true,
+                "--sim-latency-ms" => cli.sim_latency_ms = Some(value()?.parse()?),
+                "--sim-jitter-ms" => cli.sim_jitter_ms = Some(value()?.parse()?),
+                "--sim-packet-loss" => cli.sim_packet_loss = Some(value()?.parse()?),
+                _ => return Err(format!("unknown argument: {arg}").into()),
+            }
+        }
+
+        Ok(cli)
+    }
+
+    /// Network conditions to simulate, if any were asked for
+    pub fn network_simulation(&self) -> Option<NetworkSimulation> {
+        if self.sim_latency_ms.is_none()
+            && self.sim_jitter_ms.is_none()
+            && self.sim_packet_loss.is_none()
+        {
+            return None;
+        }
+
+        Some(NetworkSimulation {
+            latency_ms: self.sim_latency_ms.unwrap_or(0),
+            jitter_ms: self.sim_jitter_ms.unwrap_or(0),
+            packet_loss: self.sim_packet_loss.unwrap_or(0.0).clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,96 @@
+//! On-screen readout of the rolled back state, only in debug builds. Toggle it with F3.
+
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    game::{DeathStack, FrameCount, Player, RollbackState, Scores},
+};
+
+#[derive(Component)]
+struct DebugInspector;
+
+pub struct DebugInspectorPlugin;
+
+impl Plugin for DebugInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (toggle_inspector, update_inspector)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+fn toggle_inspector(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    inspector: Query<Entity, With<DebugInspector>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+
+    if let Ok(entity) = inspector.single() {
+        commands.entity(entity).despawn();
+        return;
+    }
+
+    commands.spawn((
+        DebugInspector,
+        DespawnOnExit(GameState::Playing),
+        Node {
+            position_type: PositionType::Absolute,
+            left: px(8),
+            bottom: px(8),
+            padding: UiRect::all(px(8)),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        Text::default(),
+        TextFont {
+            font_size: 16.,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        GlobalZIndex(20),
+    ));
+}
+
+fn update_inspector(
+    mut inspector: Query<&mut Text, With<DebugInspector>>,
+    frame_count: Res<FrameCount>,
+    round_state: Res<State<RollbackState>>,
+    scores: Res<Scores>,
+    death_stack: Res<DeathStack>,
+    players: Query<&Player>,
+) {
+    let Ok(mut text) = inspector.single_mut() else {
+        return;
+    };
+
+    let mut scores: Vec<_> = scores.iter().collect();
+    scores.sort();
+    let mut players: Vec<&Player> = players.iter().collect();
+    players.sort_by_key(|p| p.handle);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "frame: {}", frame_count.frame);
+    let _ = writeln!(out, "round: {:?}", round_state.get());
+    let _ = writeln!(out, "scores: {scores:?}");
+    let _ = writeln!(out, "death stack: {:?}", **death_stack);
+    for p in players {
+        let _ = writeln!(
+            out,
+            "player {}: fuel {:.1} hovering {} dashing {} cooldown {} grace {}",
+            p.handle, p.fuel, p.hovering, p.dashing, p.dash_cooldown, p.spawn_grace
+        );
+    }
+
+    if text.0 != out {
+        text.0 = out;
+    }
+}
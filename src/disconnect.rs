@@ -0,0 +1,160 @@
+//! Overlay for when the match goes on without us, because every other player dropped out of the
+//! GGRS session or the room we matched in is gone. GGRS keeps simulating disconnected players
+//! with empty inputs, so otherwise we'd be left racing players that never turn. There's no
+//! rejoining as a spectator, a running session can't take new ones, so all this offers is the way
+//! back to picking a room.
+
+use bevy::{platform::collections::HashSet, prelude::*};
+use bevy_ggrs::ggrs::{P2PSession, PlayerHandle};
+use bevy_matchbox::prelude::*;
+
+use crate::{GameState, game::GameConfig, lobby_config::button, pause_menu::PauseMenu};
+
+/// Why we're on our own
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Lost {
+    /// Every remote player disconnected from the session
+    Players,
+    /// The signalling server closed the room
+    Room,
+}
+
+/// Remote players GGRS has told us are gone. Reset by
+/// [`teardown_match`](crate::lobby::teardown_match), a session they left is never used again.
+#[derive(Resource, Default, Debug)]
+pub struct Disconnects {
+    handles: HashSet<PlayerHandle>,
+    lost: Option<Lost>,
+}
+
+impl Disconnects {
+    /// Records a [`GgrsEvent::Disconnected`](bevy_ggrs::ggrs::GgrsEvent::Disconnected) for `peer`
+    pub fn peer_disconnected(&mut self, session: &P2PSession<GameConfig>, peer: PeerId) {
+        let num_players = session.num_players();
+        // Observers disconnect through here too, only players count
+        self.handles.extend(
+            session
+                .handles_by_address(peer)
+                .into_iter()
+                .filter(|handle| *handle < num_players),
+        );
+
+        let remotes = num_players - session.local_player_handles().len();
+        if self.lost.is_none() && remotes > 0 && self.handles.len() >= remotes {
+            self.lost = Some(Lost::Players);
+        }
+    }
+}
+
+#[derive(Component)]
+struct DisconnectOverlay;
+
+#[derive(Component)]
+struct LeaveButton;
+
+pub struct DisconnectPlugin;
+
+impl Plugin for DisconnectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Disconnects>().add_systems(
+            Update,
+            (
+                watch_room.run_if(resource_exists::<MatchboxSocket>),
+                spawn_disconnect_overlay,
+                leave_after_disconnect,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// The lobby stops checking on the socket once the match starts, so nothing else would notice the
+/// signalling server going away
+fn watch_room(mut socket: ResMut<MatchboxSocket>, mut disconnects: ResMut<Disconnects>) {
+    if disconnects.lost.is_some() {
+        return;
+    }
+
+    if socket.try_update_peers().is_err() {
+        warn!("room closed mid-match");
+        disconnects.lost = Some(Lost::Room);
+    }
+}
+
+fn spawn_disconnect_overlay(
+    mut commands: Commands,
+    disconnects: Res<Disconnects>,
+    existing: Query<(), With<DisconnectOverlay>>,
+) {
+    let Some(lost) = disconnects.lost else {
+        return;
+    };
+    if !existing.is_empty() {
+        return;
+    }
+
+    let reason = match lost {
+        Lost::Players => "Everyone else left the match",
+        Lost::Room => "The room is gone",
+    };
+
+    commands.spawn((
+        DisconnectOverlay,
+        DespawnOnExit(GameState::Playing),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: px(16),
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        // Under the pause menu, so it can still be opened to change settings
+        GlobalZIndex(9),
+        children![
+            (
+                Text::new("You disconnected"),
+                TextFont {
+                    font_size: 48.,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.85, 0.2)),
+            ),
+            (
+                Text::new(reason),
+                TextFont {
+                    font_size: 28.,
+                    ..default()
+                },
+            ),
+            button("Leave", LeaveButton),
+        ],
+    ));
+}
+
+/// Enter or the button goes back to picking a room
+fn leave_after_disconnect(
+    disconnects: Res<Disconnects>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pause_menu: Res<PauseMenu>,
+    interactions: Query<&Interaction, (Changed<Interaction>, With<LeaveButton>)>,
+    mut app_state: ResMut<NextState<GameState>>,
+) {
+    if disconnects.lost.is_none() {
+        return;
+    }
+
+    // Enter belongs to the pause menu while it's open
+    let entered = *pause_menu == PauseMenu::Closed && keyboard_input.just_pressed(KeyCode::Enter);
+    let clicked = interactions
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+    if entered || clicked {
+        // Entering it queues teardown_match, same as leaving from the pause menu
+        app_state.set(GameState::LobbyConfig);
+    }
+}
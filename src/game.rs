@@ -1,20 +1,59 @@
-use std::{borrow::Cow, f32::consts::PI, time::Duration};
+use std::{borrow::Cow, f32::consts::PI, hash::BuildHasher};
 
-use bevy::{platform::collections::HashMap, prelude::*};
-use bevy_ggrs::{LocalInputs, LocalPlayers, prelude::*};
+use bevy::{
+    ecs::system::SystemParam,
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    log::tracing::Span,
+    math::ops,
+    platform::{collections::HashMap, hash::FixedHasher},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_ggrs::{
+    ConfirmedFrameCount, LocalInputs, LocalPlayers, RollbackFrameCount, ggrs::InputStatus,
+    prelude::*,
+};
 use bevy_matchbox::prelude::*;
 use bevy_roll_safe::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{FPS, GameState};
+use crate::{
+    FPS, GameState,
+    pause_menu::PauseMenu,
+    physics_config::{ArenaEdge, CasualRules, PhysicsConfig, PlayerCollision, RoundTransition},
+    settings::{
+        AccessibilitySettings, AudioSettings, CameraMode, CameraSettings, ControlSettings,
+        DeathEffects, GraphicsSettings, HudSettings, OverviewOrbit, RoundEndTrails,
+        ScoreboardOrder, SoundKind,
+    },
+};
 
-const INPUT_JUMP: u8 = 1 << 0;
-const INPUT_LEFT: u8 = 1 << 1;
-const INPUT_RIGHT: u8 = 1 << 2;
-const INPUT_DASH: u8 = 1 << 3;
+const INPUT_JUMP: u16 = 1 << 0;
+const INPUT_LEFT: u16 = 1 << 1;
+const INPUT_RIGHT: u16 = 1 << 2;
+const INPUT_DASH: u16 = 1 << 3;
+const INPUT_HOVER: u16 = 1 << 4;
+const INPUT_CONTINUE: u16 = 1 << 5;
+const INPUT_FORFEIT: u16 = 1 << 6;
+const INPUT_BRAKE: u16 = 1 << 9;
+const INPUT_PAUSE: u16 = 1 << 10;
+const INPUT_SEPARATE_HOVER: u16 = 1 << 11;
+/// Two bits of how hard to turn, see [`Input::turn_strength`]
+const INPUT_TURN_LEVEL_SHIFT: u16 = 7;
+const INPUT_TURN_LEVEL: u16 = 0b11 << INPUT_TURN_LEVEL_SHIFT;
+/// Number of partial turn strengths an analog stick is rounded to, the rest is full strength
+const TURN_LEVELS: u16 = 4;
+/// How far a stick has to be pushed before it turns at all
+const STICK_DEADZONE: f32 = 0.15;
 
 const SPHERE_RADIUS: f32 = 4.0;
 const SPHERE_RADIUS_SQ: f32 = SPHERE_RADIUS * SPHERE_RADIUS;
+/// How close to the surface, in squared distance from the center past [`SPHERE_RADIUS_SQ`], an
+/// airborne player has to get to land
+const GROUND_ENTER_EPSILON: f32 = 0.02;
+/// How far past [`SPHERE_RADIUS_SQ`] a grounded player can drift before they're airborne. Looser
+/// than [`GROUND_ENTER_EPSILON`] so someone right at the surface doesn't flicker in and out.
+const GROUND_EXIT_EPSILON: f32 = 0.06;
 const MOVE_SPEED: f32 = 5.0;
 const TURN_SPEED: f32 = 0.75;
 const GRAVITY: f32 = -75.0;
@@ -24,11 +63,50 @@ const FUEL_REGEN: f32 = 1. / 3.;
 const DASH_SPEED_MULTIPLIER: f32 = 2.0;
 const DASH_LENGTH: f32 = 0.7;
 const DASH_COOLDOWN: f32 = 4.0;
+const DASH_LENGTH_FRAMES: u32 = (DASH_LENGTH * FPS as f32).round() as u32;
+const DASH_COOLDOWN_FRAMES: u32 = (DASH_COOLDOWN * FPS as f32).round() as u32;
 const PLAYER_RADIUS: f32 = 0.18;
 const TRAIL_RADIUS: f32 = 0.2;
-const TRAIL_SPAWN_DIST: f32 = TRAIL_RADIUS / 2.0;
-/// Trail must exist for this many seconds before it kills people
-const MIN_TRAIL_LIFE: f64 = 0.07;
+/// Spots considered for a player respawning in [`PhysicsConfig::casual`], spread evenly over the
+/// sphere
+const RESPAWN_CANDIDATES: usize = 32;
+/// Frames a trail has to exist before it kills the player who left it, about 0.07s. It's deadly
+/// to everyone else straight away.
+const MIN_TRAIL_LIFE_FRAMES: i32 = (FPS / 15) as i32;
+/// Number of materials each player's trail steps through as it ages, see `fade_trails_by_age`
+const TRAIL_AGE_BANDS: usize = 4;
+/// Alpha of the oldest band
+const MIN_TRAIL_AGE_ALPHA: f32 = 0.3;
+/// How much thinner segments laid while dashing are drawn. Only how they look, they kill at the
+/// full trail radius like any other segment.
+const DASH_TRAIL_WIDTH: f32 = 0.6;
+/// How far towards white segments laid while dashing are tinted
+const DASH_TRAIL_BRIGHTEN: f32 = 0.4;
+/// Size of a trail eraser pickup, players pick it up by touching it
+const ERASER_RADIUS: f32 = 0.25;
+/// How long the screen flashes for when someone uses an eraser
+const ERASER_FLASH_SECS: f32 = 0.3;
+/// Half the thickness of the rings marking the edges of [`PhysicsConfig::arena_band`]
+const ARENA_EDGE_WIDTH: f32 = 0.03;
+/// Size of a boost pad, players get boosted by touching it
+const BOOST_PAD_RADIUS: f32 = 0.3;
+/// How long a boost pad pulses for when someone uses it
+const BOOST_PAD_PULSE_SECS: f32 = 0.4;
+/// How long the arrows showing which way everyone starts off facing take to fade out
+const SPAWN_INDICATOR_SECS: f32 = 1.5;
+/// How long before the match time limit the arena starts warming up, see `show_overtime`
+const OVERTIME_RAMP_SECS: u32 = 30;
+/// How long the burst left where a player dies lasts, see [`DeathEffects`]
+const DEATH_BURST_SECS: f32 = 0.6;
+/// Sparks in a [`DeathEffects::Full`] burst
+const DEATH_SPARKS: usize = 12;
+/// How long the trail segment that killed the local player glows for
+const KILL_HIGHLIGHT_SECS: f32 = 1.5;
+/// How long Ctrl+Backspace has to be held to forfeit the round
+const FORFEIT_HOLD_SECS: f32 = 1.5;
+/// How long the game has to be stuck waiting on a peer before we say so, so ordinary hiccups
+/// don't flash it up
+const SLOW_PEER_SECS: f32 = 0.25;
 
 struct SlotInfo {
     #[allow(unused)]
@@ -62,35 +140,222 @@ const SLOT_INFO: [SlotInfo; 6] = [
     },
 ];
 
+/// Trail color for a player: the classic palette for up to 6 players, evenly spaced hues beyond
+pub fn slot_color(handle: usize, num_players: usize) -> Color {
+    if num_players <= SLOT_INFO.len() {
+        SLOT_INFO[handle].color
+    } else {
+        Color::hsl(360.0 * handle as f32 / num_players as f32, 1.0, 0.5)
+    }
+}
+
 // You need to define a config struct to bundle all the generics of GGRS. bevy_ggrs provides a sensible default in `GgrsConfig`.
 // (optional) You can define a type here for brevity.
 pub type GameConfig = GgrsConfig<Input, PeerId>;
 
 #[derive(States, Clone, Eq, PartialEq, Debug, Hash, Default, Reflect)]
-enum RollbackState {
+pub enum RollbackState {
     #[default]
     None,
     /// When the characters are running around
     InRound,
     /// When one character is left, and we're transitioning to the next round
     RoundEnd,
+    /// The match is over and someone won
+    MatchEnd,
 }
 
+/// One frame of a player's input, sent to every peer so it's kept to two bytes.
+///
+/// | bit  | meaning                                                   |
+/// |------|-----------------------------------------------------------|
+/// | 0    | jump                                                      |
+/// | 1    | turn left                                                 |
+/// | 2    | turn right                                                |
+/// | 3    | dash                                                      |
+/// | 4    | hover                                                     |
+/// | 5    | continue to next round                                    |
+/// | 6    | forfeit the round                                         |
+/// | 7-8  | turn strength, 0 for full or 1-3 quarters of it (analog)  |
+/// | 9    | brake, slowing to the minimum speed                       |
+/// | 10   | pause or resume the round for everyone                    |
+/// | 11   | hover has its own key, instead of holding jump            |
+/// | 12-15| unused                                                    |
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
-pub struct Input(u8);
+pub struct Input(u16);
+
+impl Input {
+    fn has(self, bit: u16) -> bool {
+        self.0 & bit != 0
+    }
+
+    fn with(self, bit: u16, pressed: bool) -> Self {
+        if pressed {
+            Input(self.0 | bit)
+        } else {
+            Input(self.0 & !bit)
+        }
+    }
+
+    pub fn is_jump(self) -> bool {
+        self.has(INPUT_JUMP)
+    }
+
+    pub fn is_left(self) -> bool {
+        self.has(INPUT_LEFT)
+    }
+
+    pub fn is_right(self) -> bool {
+        self.has(INPUT_RIGHT)
+    }
+
+    pub fn is_dash(self) -> bool {
+        self.has(INPUT_DASH)
+    }
+
+    pub fn is_hover(self) -> bool {
+        self.has(INPUT_HOVER)
+    }
+
+    pub fn is_continue(self) -> bool {
+        self.has(INPUT_CONTINUE)
+    }
+
+    pub fn is_forfeit(self) -> bool {
+        self.has(INPUT_FORFEIT)
+    }
+
+    pub fn is_brake(self) -> bool {
+        self.has(INPUT_BRAKE)
+    }
+
+    pub fn is_pause(self) -> bool {
+        self.has(INPUT_PAUSE)
+    }
+
+    /// Whether [`is_hover`](Self::is_hover) comes from its own key, see
+    /// [`ControlSettings::hover_key`]
+    pub fn is_separate_hover(self) -> bool {
+        self.has(INPUT_SEPARATE_HOVER)
+    }
+
+    pub fn with_jump(self, pressed: bool) -> Self {
+        self.with(INPUT_JUMP, pressed)
+    }
+
+    pub fn with_left(self, pressed: bool) -> Self {
+        self.with(INPUT_LEFT, pressed)
+    }
+
+    pub fn with_right(self, pressed: bool) -> Self {
+        self.with(INPUT_RIGHT, pressed)
+    }
+
+    pub fn with_dash(self, pressed: bool) -> Self {
+        self.with(INPUT_DASH, pressed)
+    }
+
+    pub fn with_hover(self, pressed: bool) -> Self {
+        self.with(INPUT_HOVER, pressed)
+    }
+
+    pub fn with_continue(self, pressed: bool) -> Self {
+        self.with(INPUT_CONTINUE, pressed)
+    }
+
+    pub fn with_forfeit(self, pressed: bool) -> Self {
+        self.with(INPUT_FORFEIT, pressed)
+    }
+
+    pub fn with_brake(self, pressed: bool) -> Self {
+        self.with(INPUT_BRAKE, pressed)
+    }
+
+    pub fn with_pause(self, pressed: bool) -> Self {
+        self.with(INPUT_PAUSE, pressed)
+    }
+
+    pub fn with_separate_hover(self, separate: bool) -> Self {
+        self.with(INPUT_SEPARATE_HOVER, separate)
+    }
+
+    /// How much of the full turn speed to turn at, from 0 to 1. Keys always turn at full speed,
+    /// a stick in analog mode turns in quarters.
+    pub fn turn_strength(self) -> f32 {
+        match (self.0 & INPUT_TURN_LEVEL) >> INPUT_TURN_LEVEL_SHIFT {
+            0 => 1.0,
+            level => level as f32 / TURN_LEVELS as f32,
+        }
+    }
+
+    /// Turns by a stick's x axis, rounding to the nearest [`TURN_LEVELS`] step so it fits in the
+    /// input and every peer turns by exactly the same amount
+    pub fn with_analog_turn(self, x: f32) -> Self {
+        let magnitude = x.abs().min(1.0);
+        if magnitude < STICK_DEADZONE {
+            return self.with_left(false).with_right(false);
+        }
+
+        // Full strength wraps around to 0, the same as a key
+        let level = ((magnitude * TURN_LEVELS as f32).round() as u16).max(1) % TURN_LEVELS;
+        Input((self.0 & !INPUT_TURN_LEVEL) | (level << INPUT_TURN_LEVEL_SHIFT))
+            .with_left(x < 0.0)
+            .with_right(x > 0.0)
+    }
+}
+
+impl From<u16> for Input {
+    fn from(bits: u16) -> Self {
+        Input(bits)
+    }
+}
+
+impl From<Input> for u16 {
+    fn from(input: Input) -> Self {
+        input.0
+    }
+}
 
 #[derive(Default, Component, Clone)]
 pub struct Player {
     pub handle: usize,
     pub fuel: f32,
     pub hovering: bool,
-    pub dashing: Timer,
-    pub dash_cooldown: Timer,
+    /// Holding brake this frame, see [`PhysicsConfig::brake_factor`]
+    pub braking: bool,
+    /// Frames of dash left, dashing while non-zero
+    pub dashing: u32,
+    /// Frames until dash can be used again
+    pub dash_cooldown: u32,
+    /// Frames of boost from a [`BoostPad`] left
+    pub boost: u32,
+    /// Frames left after spawning during which trails can't kill this player
+    pub spawn_grace: u32,
+    /// Current turn rate in radians per second, only lags behind input while drifting
+    pub turn_velocity: f32,
+    /// On the ground as of the last check in [`move_player`], see [`Player::update_grounded`]
+    pub grounded: bool,
     pub last_trail_pos: Vec3,
     pub last_trail: Option<Entity>,
 }
 
+impl Player {
+    /// Updates [`Player::grounded`] for a player at `translation` moving outwards at
+    /// `vertical_velocity`, and returns it. Landing and leaving use different thresholds, and
+    /// moving away from the surface always counts as leaving it.
+    fn update_grounded(&mut self, translation: Vec3, vertical_velocity: f32) -> bool {
+        let epsilon = if self.grounded {
+            GROUND_EXIT_EPSILON
+        } else {
+            GROUND_ENTER_EPSILON
+        };
+        self.grounded =
+            vertical_velocity <= 0.0 && translation.length_squared() <= SPHERE_RADIUS_SQ + epsilon;
+        self.grounded
+    }
+}
+
 // Components that should be saved/loaded need to support snapshotting. The built-in options are:
 // - Clone (Recommended)
 // - Copy
@@ -101,592 +366,5731 @@ struct Velocity(Vec3);
 
 #[derive(Default, Clone, Copy, Component)]
 struct TrailSegment {
+    /// Handle of the player that left this segment
+    owner: usize,
     created_at: f64,
+    /// [`NetPause::running_frame`] the segment was left on, see
+    /// [`PhysicsConfig::trail_shrink_frames`]
+    created_frame: i32,
+    /// Distance between its ends, all the way from the owner's previous segment however far
+    /// they moved since, so fast movement like dashing never leaves a gap to slip through
+    length: f32,
+    /// Whether the owner was dashing when they left it, so dashes can be read off trails
+    dashed: bool,
+}
+
+impl TrailSegment {
+    /// How much of its full radius the segment has left on `running_frame`, from 1 down to 0
+    fn thickness(&self, running_frame: i32, physics: &PhysicsConfig) -> f32 {
+        let Some(shrink_frames) = physics.trail_shrink_frames else {
+            return 1.0;
+        };
+        let age = (running_frame - self.created_frame) as f32;
+        (1.0 - age / shrink_frames.max(1) as f32).clamp(0.0, 1.0)
+    }
+}
+
+/// Pickup that erases nearby trails, see [`PhysicsConfig::eraser_spawn_chance`]
+#[derive(Default, Clone, Copy, Component)]
+struct TrailEraser;
+
+/// Pad that speeds up whoever runs over it, see [`PhysicsConfig::boost_pads`]. Never moves or
+/// goes away during a match, so it isn't rolled back.
+#[derive(Component)]
+struct BoostPad {
+    index: usize,
 }
 
+/// Grows a [`BoostPad`] for a moment after it's used
+#[derive(Component)]
+struct BoostPadPulse(Timer);
+
 // You can also register resources.
 #[derive(Resource, Default, Reflect, Hash, Clone, Copy)]
 #[reflect(Hash)]
-struct FrameCount {
-    frame: u32,
+pub struct FrameCount {
+    pub frame: u32,
 }
 
-#[derive(Component)]
-struct Scoreboard;
+/// Deterministic random numbers for the simulation. Seeded the same on every peer and rolled
+/// back, so every peer draws the same numbers in the same order.
+///
+/// That only holds while every draw depends on nothing but simulated state and the match's
+/// config, never on query order, how many entities a query happens to return, or anything local
+/// like settings or wall time. Today the only systems that draw are:
+///
+/// - [`spawn_players`], once per round: shuffling every slot, which draws one less time than
+///   there are players in the session however many are alive, and one draw for [`Wind`] if it's
+///   on
+/// - [`spawn_trail_eraser`], once per frame while there's no eraser, and three more when one
+///   drops
+///
+/// Scoring ([`check_round_end`], [`run_casual_round`]) and respawning ([`safe_spawn`]) never
+/// draw, so placements only ever depend on [`DeathStack`]. [`BoostPad`]s are placed once when the
+/// match is set up, from a copy, so they don't use up draws either. It's checksummed, so a stray
+/// draw shows up as a desync on the frame it happened, and `--synctest` catches it across
+/// rollbacks.
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy)]
+#[reflect(Hash)]
+pub struct GameRng {
+    state: u64,
+}
 
-#[derive(Resource, Clone, Deref, DerefMut)]
-struct RoundEndTimer(Timer);
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng { state: seed }
+    }
 
-/// Map from player handle to score
-#[derive(Resource, Default, Clone, Deref, DerefMut)]
-struct Scores(HashMap<usize, u32>);
+    /// splitmix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
 
-/// Stack tracking the death order
-#[derive(Resource, Default, Clone, Deref, DerefMut)]
-struct DeathStack(Vec<usize>);
+    /// Random index in `0..len`
+    fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
 
-impl Default for RoundEndTimer {
-    fn default() -> Self {
-        RoundEndTimer(Timer::from_seconds(0.75, TimerMode::Repeating))
+    /// True with the given probability
+    fn chance(&mut self, probability: f32) -> bool {
+        // 24 bits is as precise as the f32 probability anyway
+        ((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) < probability
     }
-}
 
-pub struct GamePlugin;
+    /// Random point on the sphere's surface, not perfectly uniform but close enough for pickups
+    fn point_on_sphere(&mut self) -> Vec3 {
+        let mut coord = || (self.next_u64() >> 40) as f32 / (1u64 << 23) as f32 - 1.0;
+        let direction = Vec3::new(coord(), coord(), coord());
+        direction.try_normalize().unwrap_or(Vec3::Y) * SPHERE_RADIUS
+    }
 
-impl Plugin for GamePlugin {
-    fn build(&self, app: &mut App) {
-        app.add_plugins((
-            GgrsPlugin::<GameConfig>::default(),
-            RollbackSchedulePlugin::new_ggrs(),
-        ))
-        .init_ggrs_state::<RollbackState>()
-        // define frequency of rollback game logic update
-        .insert_resource(RollbackFrameRate(FPS))
-        .init_resource::<RoundEndTimer>()
-        .init_resource::<Scores>()
-        .init_resource::<DeathStack>()
-        // this system will be executed as part of input reading
-        .add_systems(ReadInputs, read_local_inputs)
-        // Rollback behavior can be customized using a variety of extension methods and plugins:
-        // The FrameCount resource implements Copy, we can use that to have minimal overhead rollback
-        .rollback_resource_with_copy::<FrameCount>()
-        // Same with the Velocity Component
-        .rollback_component_with_copy::<Velocity>()
-        // Transform only implements Clone, so instead we'll use that to snapshot and rollback with
-        .rollback_component_with_clone::<Transform>()
-        .rollback_component_with_copy::<TrailSegment>()
-        .rollback_component_with_clone::<Player>()
-        .rollback_component_with_clone::<SceneRoot>()
-        .rollback_resource_with_clone::<RoundEndTimer>()
-        .rollback_resource_with_clone::<Scores>()
-        .rollback_resource_with_clone::<DeathStack>()
-        // register a resource that will be rolled back
-        .insert_resource(FrameCount { frame: 0 })
-        .add_systems(OnEnter(GameState::Playing), setup_env)
-        .add_systems(
-            OnEnter(RollbackState::InRound),
-            (spawn_players, update_scoreboard).chain(),
-        )
-        // these systems will be executed as part of the advance frame update
-        .add_systems(
-            RollbackUpdate,
-            (
-                move_player,
-                manage_trail.after(move_player),
-                move_camera.after(manage_trail),
-                check_collisions.after(move_camera),
-                check_round_end.after(check_collisions),
-            )
-                .run_if(in_state(RollbackState::InRound))
-                .after(bevy_roll_safe::apply_state_transition::<RollbackState>),
-        )
-        .add_systems(
-            RollbackUpdate,
-            round_end_timeout
-                .ambiguous_with(check_round_end)
-                .run_if(in_state(RollbackState::RoundEnd)),
-        );
+    /// Fisher-Yates shuffle
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.index(i + 1);
+            items.swap(i, j);
+        }
     }
 }
 
-/// Collects player inputs during [`ReadInputs`](`bevy_ggrs::ReadInputs`) and creates a [`LocalInputs`] resource.
-pub fn read_local_inputs(
-    mut commands: Commands,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    local_players: Res<LocalPlayers>,
-) {
-    let mut local_inputs = HashMap::new();
+/// Which way the wind blows this round, see [`wind_at`]. Drawn from [`GameRng`] when the round
+/// starts.
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy)]
+#[reflect(Hash)]
+struct Wind {
+    seed: u16,
+}
 
-    for handle in &local_players.0 {
-        let mut input: u8 = 0;
+/// Twist on the rules for one round, voted on between rounds when
+/// [`PhysicsConfig::modifier_vote`] is on
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Hash)]
+pub enum RoundModifier {
+    #[default]
+    None,
+    LowGravity,
+    ThickTrails,
+    FastDash,
+}
 
-        if keyboard_input.pressed(KeyCode::ArrowLeft) {
-            input |= INPUT_LEFT;
+impl RoundModifier {
+    /// Every modifier, in the order ties are broken
+    const ALL: [RoundModifier; 4] = [
+        RoundModifier::None,
+        RoundModifier::LowGravity,
+        RoundModifier::ThickTrails,
+        RoundModifier::FastDash,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            RoundModifier::None => "No change",
+            RoundModifier::LowGravity => "Low gravity",
+            RoundModifier::ThickTrails => "Thick trails",
+            RoundModifier::FastDash => "Fast dash",
         }
-        if keyboard_input.pressed(KeyCode::ArrowRight) {
-            input |= INPUT_RIGHT;
+    }
+
+    /// Key that votes for this modifier
+    fn key_name(self) -> &'static str {
+        match self {
+            RoundModifier::None => "Jump",
+            RoundModifier::LowGravity => "Left",
+            RoundModifier::ThickTrails => "Right",
+            RoundModifier::FastDash => "Dash",
         }
-        if keyboard_input.pressed(KeyCode::Space) {
-            input |= INPUT_JUMP;
+    }
+
+    /// Which modifier an input votes for, if any
+    fn voted_by(input: Input) -> Option<RoundModifier> {
+        if input.is_jump() {
+            Some(RoundModifier::None)
+        } else if input.is_left() {
+            Some(RoundModifier::LowGravity)
+        } else if input.is_right() {
+            Some(RoundModifier::ThickTrails)
+        } else if input.is_dash() {
+            Some(RoundModifier::FastDash)
+        } else {
+            None
         }
-        if keyboard_input.pressed(KeyCode::KeyZ) {
-            input |= INPUT_DASH;
+    }
+
+    fn gravity(self) -> f32 {
+        match self {
+            RoundModifier::LowGravity => GRAVITY * 0.5,
+            _ => GRAVITY,
         }
+    }
 
-        local_inputs.insert(*handle, Input(input));
+    fn trail_radius(self) -> f32 {
+        match self {
+            RoundModifier::ThickTrails => TRAIL_RADIUS * 2.0,
+            _ => TRAIL_RADIUS,
+        }
     }
 
-    commands.insert_resource(LocalInputs::<GameConfig>(local_inputs));
+    /// Multiplier on top of however fast a dash normally is
+    fn dash_boost(self) -> f32 {
+        match self {
+            RoundModifier::FastDash => 1.5,
+            _ => 1.0,
+        }
+    }
 }
 
-/// Setup sphere and lights then set rollback state to in round
-fn setup_env(
-    mut commands: Commands,
-    session: Res<Session<GameConfig>>,
-    mut scores: ResMut<Scores>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut ambient_light: ResMut<GlobalAmbientLight>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut next_state: ResMut<NextState<RollbackState>>,
-) {
-    let num_players = match &*session {
-        Session::SyncTest(s) => s.num_players(),
-        Session::P2P(s) => s.num_players(),
-        Session::Spectator(s) => s.num_players(),
-    };
+/// A small edge a player can take for themselves in the lobby, so newcomers can keep up with
+/// more experienced players
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Handicap {
+    #[default]
+    None,
+    /// Moves a little faster
+    Speed,
+    /// Hover fuel refills faster
+    Fuel,
+    /// Dashes last longer
+    Dash,
+}
 
-    // Reset and init scores
-    scores.clear();
-    for handle in 0..num_players {
-        scores.insert(handle, 0);
+impl Handicap {
+    pub const ALL: [Handicap; 4] = [
+        Handicap::None,
+        Handicap::Speed,
+        Handicap::Fuel,
+        Handicap::Dash,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Handicap::None => "None",
+            Handicap::Speed => "+10% speed",
+            Handicap::Fuel => "+50% fuel regen",
+            Handicap::Dash => "+30% dash length",
+        }
     }
 
-    // Scoreboard text
-    commands.spawn((
-        Node {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            position_type: PositionType::Absolute,
-            justify_content: JustifyContent::FlexStart,
-            align_items: AlignItems::FlexStart,
-            flex_direction: FlexDirection::Column,
-            ..default()
-        },
-        BackgroundColor(Color::NONE),
-        children![(
-            Node {
-                align_self: AlignSelf::Center,
-                justify_content: JustifyContent::Center,
-                ..Default::default()
-            },
-            Text::new(scoreboard_text(&scores)),
-            TextFont {
-                font_size: 48.,
-                ..default()
-            },
-            TextColor(Color::WHITE),
-            Scoreboard,
-        )],
-    ));
+    /// Multiplier on how fast they move over the surface
+    fn speed(self) -> f32 {
+        match self {
+            Handicap::Speed => 1.1,
+            _ => 1.0,
+        }
+    }
 
-    // Brighten
-    ambient_light.brightness = 500.0;
+    /// Multiplier on how fast their fuel comes back
+    fn fuel_regen(self) -> f32 {
+        match self {
+            Handicap::Fuel => 1.5,
+            _ => 1.0,
+        }
+    }
 
-    // Sphere
-    commands.spawn((
-        DespawnOnExit(GameState::Playing),
-        Mesh3d(meshes.add(Sphere::new(SPHERE_RADIUS))),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: Color::srgba_u8(64, 198, 255, 104),
-            alpha_mode: AlphaMode::Blend,
-            ..Default::default()
-        })),
-        Transform {
-            translation: Vec3::ZERO,
-            ..Default::default()
-        },
-    ));
+    fn dash_frames(self) -> u32 {
+        match self {
+            Handicap::Dash => DASH_LENGTH_FRAMES * 13 / 10,
+            _ => DASH_LENGTH_FRAMES,
+        }
+    }
+}
 
-    next_state.set(RollbackState::InRound);
+/// Every player's [`Handicap`], by handle. Agreed on in the lobby before the session starts and
+/// never changed during the match, so it isn't rolled back, but it's checksummed so peers that
+/// somehow disagree find out straight away. Handles past the end have none.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct Handicaps(pub Vec<Handicap>);
+
+impl Handicaps {
+    fn get(&self, handle: usize) -> Handicap {
+        self.0.get(handle).copied().unwrap_or_default()
+    }
 }
 
-/// make sure no leftover players or trails, then spawn in players
-fn spawn_players(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    session: Res<Session<GameConfig>>,
-    players: Query<Entity, With<Player>>,
-    trails: Query<Entity, With<TrailSegment>>,
-    mut death_stack: ResMut<DeathStack>,
-) {
-    for player in players {
-        commands.entity(player).try_despawn();
+/// Each player's latest [`RoundModifier`] vote this round end, by handle. Changing your mind
+/// replaces your vote.
+#[derive(Resource, Default, Clone, Deref, DerefMut)]
+struct ModifierVotes {
+    #[deref]
+    votes: Vec<(usize, RoundModifier)>,
+    /// Which vote key each handle was holding last frame, so only new presses vote. Filled in on
+    /// the first frame of the round end, so keys still held from the round don't count.
+    held: Vec<Option<RoundModifier>>,
+}
+
+impl ModifierVotes {
+    fn count(&self, modifier: RoundModifier) -> usize {
+        self.iter().filter(|(_, vote)| *vote == modifier).count()
     }
 
-    for trail in trails {
-        commands.entity(trail).try_despawn();
+    /// Most voted modifier, ties going to whichever comes first in [`RoundModifier::ALL`]. Only
+    /// depends on the votes, never the order they came in, so every peer gets the same result.
+    fn winner(&self) -> RoundModifier {
+        let mut winner = RoundModifier::None;
+        for modifier in RoundModifier::ALL {
+            if self.count(modifier) > self.count(winner) {
+                winner = modifier;
+            }
+        }
+        winner
     }
+}
 
-    death_stack.clear();
+/// Identifies a match in logs. The same on every peer, so logs from different machines can be
+/// lined up.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchId(pub u64);
 
-    let num_players = match &*session {
-        Session::SyncTest(s) => s.num_players(),
-        Session::P2P(s) => s.num_players(),
-        Session::Spectator(s) => s.num_players(),
-    };
+/// Everything needed to tag logs with which match, frame and round state they came from
+#[derive(SystemParam)]
+pub struct MatchContext<'w> {
+    match_id: Option<Res<'w, MatchId>>,
+    frame: Option<Res<'w, RollbackFrameCount>>,
+    round_state: Option<Res<'w, State<RollbackState>>>,
+}
 
-    for handle in 0..num_players {
-        // Entities which will be rolled back can be created just like any other...
-        let mut dashing = Timer::from_seconds(DASH_LENGTH, TimerMode::Once);
-        dashing.finish();
-
-        let mut dash_cooldown = Timer::from_seconds(DASH_COOLDOWN, TimerMode::Once);
-        dash_cooldown.finish();
-
-        // TODO: add some way for each client to know which player is which
-        let spawn_pos = match handle {
-            0 => Vec3::new(0., SPHERE_RADIUS, 0.),
-            1 => Vec3::new(0., -SPHERE_RADIUS, 0.),
-            3 => Vec3::new(-SPHERE_RADIUS, 0., 0.),
-            2 => Vec3::new(SPHERE_RADIUS, 0., 0.),
-            4 => Vec3::new(0., 0., SPHERE_RADIUS),
-            5 => Vec3::new(0., 0., -SPHERE_RADIUS),
-            _ => panic!("Too many players!"),
-        };
-
-        let spawn_rot = match handle {
-            0 => Quat::from_rotation_y(-PI / 2.),
-            1 => Quat::from_rotation_y(PI / 2.),
-            2 => Quat::from_rotation_x(-PI / 2.),
-            3 => Quat::from_rotation_x(PI / 2.),
-            4 => Quat::from_rotation_z(-PI / 2.),
-            5 => Quat::from_rotation_z(PI / 2.),
-            _ => panic!("Too many players!"),
-        };
-
-        commands
-            .spawn((
-                DespawnOnExit(GameState::Playing),
-                Transform {
-                    translation: spawn_pos,
-                    rotation: spawn_rot,
-                    ..default()
-                },
-                Player {
-                    handle,
-                    fuel: 100.0,
-                    hovering: false,
-                    dashing,
-                    dash_cooldown,
-                    last_trail_pos: spawn_pos,
-                    last_trail: None,
-                },
-                Velocity::default(),
-                SceneRoot(
-                    asset_server
-                        .load(GltfAssetLabel::Scene(0).from_asset("models/AlienCake/alien.glb")),
-                ),
-            ))
-            .add_rollback();
+impl MatchContext<'_> {
+    /// Span to enter while logging, so records carry `match_id`, `frame` and `round` fields
+    pub fn span(&self) -> Span {
+        info_span!(
+            "match",
+            match_id = %self
+                .match_id
+                .as_deref()
+                .map_or("none".to_string(), |id| format!("{:016x}", id.0)),
+            frame = self.frame.as_deref().map_or(-1, |frame| i32::from(*frame)),
+            round = ?self.round_state.as_deref().map(State::get),
+        )
     }
 }
 
-// Example system, manipulating a resource, will be added to the rollback schedule.
-// Increases the frame count by 1 every update step. If loading and saving resources works correctly,
-// you should see this resource rolling back, counting back up and finally increasing by 1 every update step
-#[allow(dead_code)]
-fn increase_frame_system(mut frame_count: ResMut<FrameCount>) {
-    frame_count.frame += 1;
+/// Frames left before players can move this round, see
+/// [`PhysicsConfig::round_countdown_frames`]
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy, Deref, DerefMut)]
+#[reflect(Hash)]
+struct RoundCountdown(u32);
+
+/// Frames since the match started, counted during rounds and the pauses between them
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy)]
+#[reflect(Hash)]
+struct MatchClock {
+    frames: u32,
 }
 
-fn move_player(
-    query: Query<(&mut Transform, &mut Velocity, &mut Player), With<Player>>,
-    inputs: Res<PlayerInputs<GameConfig>>,
-    // Thanks to RollbackTimePlugin, this is rollback safe
-    time: Res<Time>,
-) {
-    let dt = time.delta_secs();
+impl MatchClock {
+    /// Seconds left before the match time limit, `None` without one
+    fn remaining_secs(&self, physics: &PhysicsConfig) -> Option<u32> {
+        (physics.match_time_limit_secs > 0).then(|| {
+            physics
+                .match_time_limit_secs
+                .saturating_sub(self.frames / FPS as u32)
+        })
+    }
+}
 
-    for (mut transform, mut vel, mut player) in query {
-        let inputs = inputs[player.handle].0.0;
-        let left = inputs & INPUT_LEFT != 0;
-        let right = inputs & INPUT_RIGHT != 0;
-        let jump = inputs & INPUT_JUMP != 0;
-        let dash = inputs & INPUT_DASH != 0;
-        let is_grounded = transform.translation.length_squared() <= SPHERE_RADIUS_SQ + 0.02;
-
-        // Start dashing if dash was pressed
-        player.dash_cooldown.tick(Duration::from_secs_f32(dt));
-        if dash && player.dashing.is_finished() && player.dash_cooldown.is_finished() && is_grounded
-        {
-            player.dashing.reset();
-            player.dash_cooldown.reset();
-        }
-        player.dashing.tick(Duration::from_secs_f32(dt));
+#[derive(Component)]
+struct Scoreboard;
 
-        if jump && is_grounded {
-            vel.y = JUMP_VELOCITY;
+/// How long the local player has been holding the forfeit keys. Only the finished hold is sent
+/// as input, so this isn't rolled back.
+#[derive(Resource, Default)]
+struct ForfeitHold {
+    secs: f32,
+}
 
-            // Jumping ends dash and immediately makes it available again
-            player.dashing.finish();
-            player.dash_cooldown.finish();
-        }
+/// "Keep holding to forfeit" shown while [`ForfeitHold`] fills up
+#[derive(Component)]
+struct ForfeitPrompt;
 
-        let delta_grav = GRAVITY * dt;
-        // Would start to fall on this update, if jump is held, start hovering
-        if jump
-            && vel.y.is_sign_positive()
-            && (vel.y + delta_grav).is_sign_negative()
-            && !is_grounded
-            && !player.hovering
-        {
-            player.hovering = true;
-        }
+/// The sphere everyone runs around on
+#[derive(Component)]
+struct Arena;
 
-        // Decrement fuel while hovering
-        if player.hovering && jump {
-            player.fuel -= FUEL_USAGE * dt;
-        }
+/// "PAUSED by Player N" shown while a [`NetPause`] is on
+#[derive(Component)]
+struct NetPauseText;
 
-        // Stop hover if jump is released or out of fuel
-        if player.hovering && (!jump || player.fuel <= 0.0) {
-            player.hovering = false;
-        }
+/// "Last stand!" shown while the last two players are boosted
+#[derive(Component)]
+struct LastStandText;
 
-        // Apply Gravity if in air and not hovering
-        if !player.hovering && (!is_grounded || vel.y != 0.0) {
-            vel.y += delta_grav;
-        } else {
-            vel.y = 0.0;
-        }
+/// Whole seconds of [`RoundCountdown`] it's showing
+#[derive(Component)]
+struct RoundCountdownText(u32);
 
-        // We turn around the local Y axis (the alien's "up")
-        let turn_speed = TURN_SPEED;
-        if left {
-            transform.rotate_local_y(PI * turn_speed * dt);
-        }
+/// Shown while the game is paused waiting for a peer's inputs
+#[derive(Component)]
+struct SlowPeerText;
 
-        if right {
-            transform.rotate_local_y(-PI * turn_speed * dt);
-        }
+/// Panel listing placements, scores and who's still alive, for spectators and streams
+#[derive(Component)]
+struct Standings;
 
-        // The position vector IS the "up" vector since the sphere is centered at (0,0,0)
-        let current_pos = transform.translation;
-        let forward = transform.forward().as_vec3();
+/// Span after the scores with the time left in the match
+#[derive(Component)]
+struct MatchClockText;
 
-        // THE MATH:
-        // To move forward on a sphere, we rotate the POSITION vector
-        // around an axis that is perpendicular to both UP and FORWARD.
-        let axis = transform.right().as_vec3(); // This is the "side-to-side" axis
-        let move_speed = if player.dashing.is_finished() {
-            MOVE_SPEED
-        } else {
-            DASH_SPEED_MULTIPLIER * MOVE_SPEED
-        };
-        let move_amount = move_speed * dt;
-        let angle = move_amount / SPHERE_RADIUS; // Angle in radians
+/// Tally of [`ModifierVotes`] under the round banner
+#[derive(Component)]
+struct ModifierVoteText;
 
-        // Rotate the position vector around the side-axis
-        let rotation_delta = Quat::from_axis_angle(axis, -angle);
-        let new_pos = rotation_delta * current_pos;
-        let new_up = new_pos.normalize();
+/// Span after the match clock with the score to win and who's closest to it
+#[derive(Component)]
+struct VictoryText;
 
-        // Apply new position
-        transform.translation = new_pos;
+/// Arrow in front of a player at the start of a round, pointing the way they're headed.
+/// Presentation only, so it isn't rolled back.
+#[derive(Component)]
+struct SpawnIndicator {
+    handle: usize,
+}
 
-        // Re-orient the player to stand upright on the new position
-        // Calculate a new forward that is tangent to the sphere
-        let new_forward = rotation_delta * forward;
+#[derive(Resource, Clone, Deref, DerefMut)]
+struct RoundEndTimer(Timer);
 
-        transform.look_at(new_pos + new_forward, new_up);
+/// Player the camera follows while the local player is dead.
+/// Presentation only, so it isn't rolled back.
+#[derive(Resource, Default, PartialEq, Deref, DerefMut)]
+struct SpectateTarget(Option<usize>);
 
-        // Apply velocity along the normal (away from center)
-        transform.translation += new_up * vel.y * dt;
+/// Handles of the players that have asked to continue to the next round, in
+/// [`RoundTransition::Manual`]
+#[derive(Resource, Default, Clone, Deref, DerefMut)]
+struct ContinueVotes(Vec<usize>);
 
-        // Increment fuel while grounded
-        let is_grounded = transform.translation.length_squared() <= SPHERE_RADIUS_SQ + 0.02;
-        if is_grounded && player.fuel <= 100.0 {
-            player.fuel += FUEL_REGEN * dt;
-        }
+/// Map from player handle to score
+#[derive(Resource, Default, Clone, Deref, DerefMut)]
+pub struct Scores(HashMap<usize, u32>);
 
-        // Snap player to sphere radius if they're below
-        if transform.translation.length_squared() < SPHERE_RADIUS_SQ {
-            transform.translation = new_up * SPHERE_RADIUS;
-            vel.y = 0.0;
-        }
+/// Trail materials shared by all of a player's segments, indexed by player handle, then whether
+/// the segment was laid while dashing, then age band. Band 0 is for fresh segments, later bands
+/// are more see-through.
+/// Assets aren't part of the rollback snapshot, so these handles stay valid through rollbacks.
+#[derive(Resource, Default, Deref)]
+struct TrailMaterials(Vec<[[Handle<StandardMaterial>; TRAIL_AGE_BANDS]; 2]>);
+
+impl TrailMaterials {
+    /// Material for one of `owner`'s segments in age `band`
+    fn get(&self, owner: usize, dashed: bool, band: usize) -> Option<&Handle<StandardMaterial>> {
+        self.0.get(owner).map(|kinds| &kinds[dashed as usize][band])
     }
 }
 
-fn manage_trail(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    players: Query<(&mut Transform, &mut Player), With<Player>>,
-    time: Res<Time>,
-) {
-    for (transform, mut player) in players {
-        // Calculate distance since last segment
-        let dist = transform.translation.distance(player.last_trail_pos);
+/// Alpha of a trail age band before any round end fading
+fn trail_band_alpha(band: usize) -> f32 {
+    1.0 - (1.0 - MIN_TRAIL_AGE_ALPHA) * band as f32 / (TRAIL_AGE_BANDS - 1) as f32
+}
 
-        if dist > TRAIL_SPAWN_DIST {
-            // Calculate the midpoint between current and last position
-            let midpoint = ((transform.translation + player.last_trail_pos) / 2.0)
-                + (TRAIL_RADIUS * transform.up());
+fn trail_band_alpha_mode(band: usize) -> AlphaMode {
+    if band == 0 {
+        AlphaMode::Opaque
+    } else {
+        AlphaMode::Blend
+    }
+}
 
-            // Direction from last to current
-            let direction = (transform.translation - player.last_trail_pos).normalize();
+/// Stack tracking the death order, grouped by the frame they died on. Each group is sorted by
+/// handle and shares a placement.
+#[derive(Resource, Default, Clone, Deref, DerefMut)]
+pub struct DeathStack(Vec<Vec<usize>>);
 
-            // Create a rotation that points the Cylinder's Y-axis (top)
-            // toward the movement direction
-            let rotation = Quat::from_rotation_arc(Vec3::Y, direction);
+impl DeathStack {
+    /// Whether the round ended with the last players all dying on the same frame
+    fn is_double_ko(&self, num_alive: usize) -> bool {
+        num_alive == 0 && self.last().is_some_and(|group| group.len() > 1)
+    }
+}
 
-            let last_spawned = commands
-                .spawn((
-                    DespawnOnExit(GameState::Playing),
-                    Mesh3d(meshes.add(Cylinder::new(TRAIL_RADIUS, TRAIL_RADIUS))),
-                    MeshMaterial3d(materials.add(StandardMaterial {
-                        base_color: SLOT_INFO[player.handle].color,
-                        ..default()
-                    })),
-                    Transform {
-                        translation: midpoint,
-                        rotation,
-                        ..default()
-                    },
-                    TrailSegment {
-                        created_at: time.elapsed_secs_f64(),
-                    },
-                ))
-                .add_rollback()
-                .id();
+/// Arrow floating above the local player, so they can pick themselves out of a crowd
+#[derive(Component)]
+struct LocalPlayerMarker;
 
-            // Update the last spawn position to current position
-            player.last_trail_pos = transform.translation;
-            player.last_trail = Some(last_spawned);
-        }
-    }
+/// "YOU" label that follows the [`LocalPlayerMarker`]
+#[derive(Component)]
+struct LocalPlayerLabel;
+
+/// A player dying, with whose trail they hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Death {
+    pub handle: usize,
+    pub killer: usize,
+    /// GGRS frame it happened on, compare with [`ConfirmedFrameCount`] to know it won't be
+    /// rolled back
+    pub frame: i32,
+    /// [`NetPause::running_frame`] it happened on, for timing things that stop with the round
+    pub running_frame: i32,
+    /// [`TrailSegment::created_frame`] of the segment they hit, which with `killer` picks it out.
+    /// `None` if they didn't die to a trail.
+    pub trail_frame: Option<i32>,
+    /// Where they were when they died
+    pub position: Vec3,
 }
 
-fn check_collisions(
-    mut commands: Commands,
-    players: Query<(Entity, &Transform, &Player), With<Player>>,
-    trails: Query<(&Transform, &TrailSegment), With<TrailSegment>>,
-    mut death_stack: ResMut<DeathStack>,
-    time: Res<Time>,
-) {
-    for (entity, player_trans, player) in players {
-        for (trail_transform, segment) in trails {
-            if time.elapsed_secs_f64() - segment.created_at < MIN_TRAIL_LIFE {
-                // Don't collide with own most recently spawned segment
-                continue;
-            }
+/// Every death this match, in order. Rolled back, so effects that should only happen once per
+/// death have to wait until the death's frame is confirmed.
+#[derive(Resource, Default, Clone, Deref)]
+pub struct DeathLog(Vec<Death>);
 
-            let p = player_trans.translation;
-            let b = trail_transform.translation;
+/// A simulated log of things that happened this match, for [`NewlyConfirmed`]
+pub trait EventLog: Resource {
+    type Entry: Copy + PartialEq + Send + Sync + 'static;
 
-            // We need the direction the trail is pointing to find the ends
-            // Since we used Quat::from_rotation_arc(Vec3::Y, direction),
-            // the trail's local Y axis is its "length"
-            let trail_dir = trail_transform.up();
-            let half_height = TRAIL_SPAWN_DIST / 2.0;
+    fn entries(&self) -> &[Self::Entry];
 
-            let start = b - trail_dir * half_height;
-            let end = b + trail_dir * half_height;
+    /// GGRS frame the entry happened on
+    fn frame(entry: &Self::Entry) -> i32;
+}
 
-            // Calculate distance from point P to segment [start, end]
-            let distance = dist_to_segment(p, start, end);
+impl EventLog for DeathLog {
+    type Entry = Death;
 
-            if distance < (TRAIL_RADIUS + PLAYER_RADIUS) {
-                commands.entity(entity).try_despawn();
-                death_stack.push(player.handle);
-            }
-        }
+    fn entries(&self) -> &[Death] {
+        &self.0
+    }
+
+    fn frame(death: &Death) -> i32 {
+        death.frame
     }
 }
 
-fn dist_to_segment(p: Vec3, a: Vec3, b: Vec3) -> f32 {
-    let v = b - a;
-    let w = p - a;
-    let c1 = w.dot(v);
-    if c1 <= 0.0 {
-        return p.distance(a);
+/// Entries of an [`EventLog`] confirmed since the system last looked. Effects that should
+/// happen once per entry read them from here, so a mispredicted one never shows and a
+/// resimulated one doesn't show twice.
+#[derive(SystemParam)]
+pub struct NewlyConfirmed<'w, 's, L: EventLog> {
+    log: Res<'w, L>,
+    confirmed_frame: Option<Res<'w, ConfirmedFrameCount>>,
+    shown: Local<'s, Vec<L::Entry>>,
+}
+
+impl<L: EventLog> NewlyConfirmed<'_, '_, L> {
+    /// Entries that weren't confirmed last time, oldest first
+    pub fn read(&mut self) -> Vec<L::Entry> {
+        let entries = self.log.entries();
+        // The log starts over with each match
+        self.shown.retain(|entry| entries.contains(entry));
+
+        let Some(confirmed_frame) = self.confirmed_frame.as_deref() else {
+            return Vec::new();
+        };
+        let confirmed_frame = i32::from(*confirmed_frame);
+        let new: Vec<L::Entry> = entries
+            .iter()
+            .filter(|entry| L::frame(entry) <= confirmed_frame && !self.shown.contains(entry))
+            .copied()
+            .collect();
+        self.shown.extend_from_slice(&new);
+        new
     }
-    let c2 = v.dot(v);
-    if c2 <= c1 {
-        return p.distance(b);
+}
+
+/// Whether this round's placements have been scored. The state change to
+/// [`RollbackState::RoundEnd`] already lands before [`check_round_end`] could run again, and a
+/// rollback restores this along with [`Scores`], but this makes sure scoring happens once per
+/// round even if that ordering changes.
+#[derive(Resource, Default, Reflect, Hash, Clone, Copy, Deref, DerefMut)]
+#[reflect(Hash)]
+struct RoundScored(bool);
+
+/// A timeout called by one player that freezes the round for everyone, see
+/// [`PhysicsConfig::pauses_per_player`]. Everything is worked out from inputs in
+/// [`update_net_pause`], so every peer pauses and resumes on the same frame.
+#[derive(Resource, Default, Reflect, Hash, Clone)]
+#[reflect(Hash)]
+struct NetPause {
+    /// Who paused, while the round is paused
+    by: Option<usize>,
+    /// Frames until the round resumes by itself
+    frames_left: u32,
+    /// Pauses each handle has used this match
+    used: Vec<u32>,
+    /// Whether each handle was holding pause last frame, so holding it doesn't pause and resume
+    /// over and over
+    held: Vec<bool>,
+    /// Frames spent paused so far this match
+    paused_frames: u32,
+}
+
+impl NetPause {
+    /// `frame` with the frames spent paused taken off, so it only moves on while the round is
+    /// running. Anything timed in frames during a round counts these, or it would carry on
+    /// through a pause.
+    fn running_frame(&self, frame: RollbackFrameCount) -> i32 {
+        i32::from(frame) - self.paused_frames as i32
     }
-    let b2 = c1 / c2;
-    let pb = a + v * b2;
-    p.distance(pb)
 }
 
-fn check_round_end(
-    session: Res<Session<GameConfig>>,
-    players: Query<&Player, With<Player>>,
-    mut scores: ResMut<Scores>,
-    death_stack: Res<DeathStack>,
-    mut next_state: ResMut<NextState<RollbackState>>,
-) {
-    let num_players = match &*session {
-        Session::SyncTest(s) => s.num_players(),
-        Session::P2P(s) => s.num_players(),
-        Session::Spectator(s) => s.num_players(),
-    };
+/// Stats kept over the whole match to pick an MVP from when it ends, see [`MatchStats::mvp`].
+/// Simulated and rolled back like [`Scores`], so every peer ends up with the same numbers.
+#[derive(Resource, Default, Reflect, Hash, Clone)]
+#[reflect(Hash)]
+struct MatchStats {
+    /// Other players who died to each handle's trail or in a crash with them
+    kills: Vec<u32>,
+    /// Frames each handle has spent alive in a round
+    frames_alive: Vec<u32>,
+}
 
-    let num_players_remaining = players.count();
+impl MatchStats {
+    /// Most kills, then longest alive, then lowest handle. `None` before anything has happened.
+    fn mvp(&self) -> Option<usize> {
+        (0..self.kills.len())
+            .filter(|handle| self.kills[*handle] > 0 || self.frames_alive[*handle] > 0)
+            .max_by(|a, b| {
+                (self.kills[*a], self.frames_alive[*a])
+                    .cmp(&(self.kills[*b], self.frames_alive[*b]))
+                    // max_by keeps the last of equals, so the lower handle has to compare greater
+                    .then(b.cmp(a))
+            })
+    }
+}
 
-    if num_players_remaining <= 1 {
-        // 0 or 1 player left, game over and distribute scores
+/// Progress through a [`PhysicsConfig::casual`] round
+#[derive(Resource, Default, Clone)]
+struct CasualRound {
+    /// Frames since the round started
+    frames: u32,
+    /// Dead players and the value of `frames` they come back on
+    respawns: Vec<(usize, u32)>,
+}
 
-        let mut add_score = num_players as u32 - 1;
-        if let Ok(last_alive) = players.single() {
-            *scores.get_mut(&last_alive.handle).unwrap() += add_score;
-            add_score -= 1;
-        }
+/// Someone picking up a [`TrailEraser`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct EraserUse {
+    handle: usize,
+    /// GGRS frame it happened on, the flash waits for it to be confirmed like [`Death`]s
+    frame: i32,
+}
 
-        for handle in death_stack.iter().rev() {
-            *scores.get_mut(handle).unwrap() += add_score;
-            add_score = add_score.saturating_sub(1);
-        }
+/// Every eraser picked up this match, in order
+#[derive(Resource, Default, Clone, Deref)]
+struct EraserLog(Vec<EraserUse>);
 
-        next_state.set(RollbackState::RoundEnd);
+impl EventLog for EraserLog {
+    type Entry = EraserUse;
+
+    fn entries(&self) -> &[EraserUse] {
+        &self.0
     }
-}
 
-fn update_scoreboard(mut scoreboard: Single<&mut Text, With<Scoreboard>>, scores: Res<Scores>) {
-    scoreboard.0 = scoreboard_text(&scores);
+    fn frame(eraser: &EraserUse) -> i32 {
+        eraser.frame
+    }
 }
 
-fn scoreboard_text(scores: &HashMap<usize, u32>) -> String {
-    (0..scores.len())
-        .map(|handle| {
-            let score = scores[&handle];
-            Cow::<'static, str>::from(score.to_string())
-        })
-        .intersperse(" - ".into())
-        .collect()
+/// Someone running over a [`BoostPad`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct BoostUse {
+    handle: usize,
+    pad: usize,
+    /// GGRS frame it happened on, the cue waits for it to be confirmed like [`Death`]s
+    frame: i32,
 }
 
-fn round_end_timeout(
-    mut timer: ResMut<RoundEndTimer>,
-    mut state: ResMut<NextState<RollbackState>>,
-    time: Res<Time>,
-) {
-    timer.tick(time.delta());
+/// Every boost pad used this match, in order
+#[derive(Resource, Default, Clone, Deref)]
+struct BoostLog(Vec<BoostUse>);
 
-    if timer.just_finished() {
-        state.set(RollbackState::InRound);
+impl EventLog for BoostLog {
+    type Entry = BoostUse;
+
+    fn entries(&self) -> &[BoostUse] {
+        &self.0
+    }
+
+    fn frame(boost: &BoostUse) -> i32 {
+        boost.frame
     }
 }
 
-#[allow(clippy::type_complexity)]
-fn move_camera(
-    local_players: Res<LocalPlayers>,
-    mut transforms: ParamSet<(
-        Single<&mut Transform, With<Camera3d>>,
-        Query<(&mut Transform, &mut Velocity, &Player), With<Rollback>>,
-    )>,
-) {
-    // Find local player's transform or return
-    let Some(player_transform) = transforms
-        .p1()
-        .iter()
-        .find_map(|(transform, _, p)| local_players.0.contains(&p.handle).then_some(transform))
-        .copied()
-    else {
-        return;
-    };
+/// Shared look of every [`TrailEraser`]
+#[derive(Resource, Default)]
+struct EraserAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
 
-    let mut cam_transform = transforms.p0();
+/// Glow around the trail segment that killed the local player, so they can see what they hit.
+/// Its own entity with its own material, the segment itself is left alone.
+#[derive(Component)]
+struct KillHighlight {
+    timer: Timer,
+    material: Handle<StandardMaterial>,
+}
 
-    let player_pos = player_transform.translation;
-    let player_up = player_pos.normalize_or_zero();
+/// Sparks or a flash where a player died, fading out. Presentation only, so it isn't rolled
+/// back. Each burst gets its own material so it can fade on its own.
+#[derive(Component)]
+struct DeathBurst {
+    timer: Timer,
+    material: Handle<StandardMaterial>,
+    /// Grow instead of sending out sparks, for [`DeathEffects::Simple`]
+    expand: bool,
+}
 
-    // Position camera 10 units "back" and 4 units "up" relative to player's current orientation
-    let backwards = -player_transform.forward();
-    let cam_pos = player_pos + (backwards * 0.01) + (player_up * 8.0);
+/// One spark of a [`DeathBurst`], flying out from where they died
+#[derive(Component)]
+struct DeathSpark {
+    velocity: Vec3,
+}
+
+/// Full screen flash shown when someone uses an eraser
+#[derive(Component)]
+struct EraserFlash {
+    timer: Timer,
+    alpha: f32,
+}
+
+/// A player's model. Kept apart from the simulated [`Player`] entity so remote players can be
+/// drawn smoothly without ever touching the Transform GGRS owns. Presentation only, so it isn't
+/// rolled back.
+#[derive(Component)]
+struct PlayerVisual {
+    handle: usize,
+}
 
-    cam_transform.translation = cam_pos;
-    // Look at the player, keeping the planet's "Up" as the camera's "Up"
-    cam_transform.look_at(player_pos, player_up);
+/// Camera shake currently playing. Presentation only, so it isn't rolled back.
+#[derive(Resource, Default)]
+struct ScreenShake {
+    timer: Timer,
+    intensity: f32,
+    /// Offset applied to the camera this frame, taken back off before the next frame
+    applied: Vec3,
+}
+
+/// Camera sweep around the sphere at the start of a round. Applied on top of the follow camera
+/// and taken back off each frame the same way as [`ScreenShake`], so the follow camera keeps
+/// tracking underneath and the sweep can blend back into it.
+#[derive(Resource)]
+struct RoundIntro {
+    timer: Timer,
+    /// Where the follow camera was before the sweep moved it this frame
+    follow: Option<Transform>,
+}
+
+impl Default for RoundIntro {
+    fn default() -> Self {
+        let mut timer = Timer::default();
+        // Nothing to show until a round starts
+        timer.finish();
+        RoundIntro {
+            timer,
+            follow: None,
+        }
+    }
+}
+
+/// Shows who won the round while waiting for the next one
+#[derive(Component)]
+struct RoundBanner;
+
+impl Default for RoundEndTimer {
+    fn default() -> Self {
+        RoundEndTimer(Timer::from_seconds(0.75, TimerMode::Repeating))
+    }
+}
+
+/// Everything that's rolled back: GGRS itself, what it snapshots and checksums, and the systems
+/// that advance a frame. Kept apart from [`GamePlugin`]'s input and presentation so it can be run
+/// headless under a SyncTest session.
+struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            GgrsPlugin::<GameConfig>::default(),
+            RollbackSchedulePlugin::new_ggrs(),
+        ))
+        .init_ggrs_state::<RollbackState>()
+        // define frequency of rollback game logic update
+        .insert_resource(RollbackFrameRate(FPS))
+        .init_resource::<PhysicsConfig>()
+        .init_resource::<RoundEndTimer>()
+        .init_resource::<Scores>()
+        .init_resource::<DeathStack>()
+        .init_resource::<TrailMaterials>()
+        .init_resource::<MatchClock>()
+        .init_resource::<RoundCountdown>()
+        .init_resource::<GameRng>()
+        .init_resource::<Wind>()
+        .init_resource::<DeathLog>()
+        .init_resource::<ContinueVotes>()
+        .init_resource::<RoundModifier>()
+        .init_resource::<ModifierVotes>()
+        .init_resource::<EraserLog>()
+        .init_resource::<CasualRound>()
+        .init_resource::<NetPause>()
+        .init_resource::<Handicaps>()
+        .init_resource::<MatchStats>()
+        .init_resource::<BoostLog>()
+        .init_resource::<RoundScored>()
+        .init_resource::<EraserAssets>()
+        // Rollback behavior can be customized using a variety of extension methods and plugins:
+        // The FrameCount resource implements Copy, we can use that to have minimal overhead rollback
+        .rollback_resource_with_copy::<FrameCount>()
+        // Same with the Velocity Component
+        .rollback_component_with_copy::<Velocity>()
+        // Transform only implements Clone, so instead we'll use that to snapshot and rollback with
+        .rollback_component_with_clone::<Transform>()
+        .rollback_component_with_copy::<TrailSegment>()
+        .rollback_component_with_copy::<TrailEraser>()
+        .rollback_component_with_clone::<Player>()
+        .rollback_resource_with_clone::<RoundEndTimer>()
+        .rollback_resource_with_clone::<Scores>()
+        .rollback_resource_with_clone::<DeathStack>()
+        .rollback_resource_with_copy::<MatchClock>()
+        .rollback_resource_with_copy::<RoundCountdown>()
+        .rollback_resource_with_copy::<GameRng>()
+        .rollback_resource_with_copy::<Wind>()
+        .rollback_resource_with_clone::<DeathLog>()
+        .rollback_resource_with_clone::<ContinueVotes>()
+        .rollback_resource_with_copy::<RoundModifier>()
+        .rollback_resource_with_clone::<ModifierVotes>()
+        .rollback_resource_with_clone::<EraserLog>()
+        .rollback_resource_with_clone::<CasualRound>()
+        .rollback_resource_with_clone::<NetPause>()
+        .rollback_resource_with_clone::<MatchStats>()
+        .rollback_resource_with_clone::<BoostLog>()
+        .rollback_resource_with_copy::<RoundScored>()
+        // Checksums of everything simulated, compared between peers for desync detection and
+        // between re-simulations of the same frame in a SyncTest session
+        .checksum_component::<Transform>(checksum_transform)
+        .checksum_component::<Velocity>(checksum_velocity)
+        .checksum_component::<Player>(checksum_player)
+        .checksum_component::<TrailSegment>(checksum_trail_segment)
+        .checksum_resource::<Scores>(checksum_scores)
+        .checksum_resource::<DeathStack>(checksum_death_stack)
+        .checksum_resource_with_hash::<FrameCount>()
+        .checksum_resource_with_hash::<MatchClock>()
+        .checksum_resource_with_hash::<RoundCountdown>()
+        .checksum_resource_with_hash::<GameRng>()
+        .checksum_resource_with_hash::<Wind>()
+        .checksum_resource::<DeathLog>(checksum_death_log)
+        .checksum_resource::<ContinueVotes>(checksum_continue_votes)
+        .checksum_resource_with_hash::<RoundModifier>()
+        .checksum_resource::<ModifierVotes>(checksum_modifier_votes)
+        .checksum_resource::<EraserLog>(checksum_eraser_log)
+        .checksum_resource::<CasualRound>(checksum_casual_round)
+        .checksum_resource_with_hash::<NetPause>()
+        .checksum_resource_with_hash::<MatchStats>()
+        .checksum_resource::<Handicaps>(checksum_handicaps)
+        .checksum_resource::<BoostLog>(checksum_boost_log)
+        .checksum_resource_with_hash::<RoundScored>()
+        // register a resource that will be rolled back
+        .insert_resource(FrameCount { frame: 0 })
+        // OnEnter runs inside apply_state_transition, which has exclusive world access, so the
+        // last round's trails are despawned before the new round's first move_player, manage_trail
+        // or check_collisions, including when a rollback re-simulates the transition
+        .add_systems(OnEnter(RollbackState::InRound), spawn_players)
+        // these systems will be executed as part of the advance frame update
+        .add_systems(
+            RollbackUpdate,
+            (
+                move_player,
+                manage_trail.after(move_player),
+                check_collisions.after(manage_trail),
+                decay_dead_trails.after(check_collisions),
+                shrink_trails.after(decay_dead_trails),
+                (spawn_trail_eraser, collect_trail_eraser)
+                    .chain()
+                    .after(manage_trail)
+                    .before(check_collisions),
+                hit_boost_pads.after(move_player).before(manage_trail),
+                run_casual_round
+                    .after(check_collisions)
+                    .before(check_round_end),
+                check_round_end.after(check_collisions),
+                tally_match_stats.after(check_collisions),
+            )
+                .run_if(in_state(RollbackState::InRound))
+                .run_if(round_running)
+                .after(update_net_pause)
+                .after(bevy_roll_safe::apply_state_transition::<RollbackState>),
+        )
+        .add_systems(
+            RollbackUpdate,
+            update_net_pause
+                .run_if(in_state(RollbackState::InRound))
+                .after(bevy_roll_safe::apply_state_transition::<RollbackState>),
+        )
+        .add_systems(
+            RollbackUpdate,
+            (cast_modifier_votes, round_end_timeout)
+                .chain()
+                .ambiguous_with(check_round_end)
+                .run_if(in_state(RollbackState::RoundEnd)),
+        )
+        .add_systems(
+            RollbackUpdate,
+            tick_match_clock
+                .run_if(in_state(RollbackState::InRound).or(in_state(RollbackState::RoundEnd)))
+                .run_if(round_running)
+                .after(update_net_pause)
+                .after(check_round_end)
+                .after(round_end_timeout)
+                .after(bevy_roll_safe::apply_state_transition::<RollbackState>),
+        );
+
+        #[cfg(debug_assertions)]
+        app.add_systems(
+            OnEnter(RollbackState::InRound),
+            check_round_start.after(spawn_players),
+        );
+    }
+}
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SimulationPlugin)
+            .init_resource::<SpectateTarget>()
+            .init_resource::<ScreenShake>()
+            .init_resource::<RoundIntro>()
+            .init_resource::<ForfeitHold>()
+            // this system will be executed as part of input reading
+            .add_systems(ReadInputs, read_local_inputs)
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (setup_env, spawn_local_player_marker),
+            )
+            .add_systems(
+                OnEnter(RollbackState::InRound),
+                update_scoreboard.after(spawn_players),
+            )
+            // presentation only, trails are still cleared by spawn_players when the next round
+            // starts
+            .add_systems(
+                Update,
+                (
+                    show_round_end_trails.run_if(in_state(RollbackState::RoundEnd)),
+                    update_round_banner.run_if(in_state(GameState::Playing)),
+                    apply_hud_settings.run_if(in_state(GameState::Playing)),
+                    update_match_clock_text.run_if(in_state(GameState::Playing)),
+                    update_victory_text.run_if(in_state(GameState::Playing)),
+                    update_scoreboard
+                        .run_if(in_state(GameState::Playing))
+                        .run_if(resource_changed::<HudSettings>),
+                    update_modifier_vote_text.run_if(in_state(RollbackState::RoundEnd)),
+                    show_last_stand.run_if(in_state(GameState::Playing)),
+                    show_round_countdown.run_if(in_state(GameState::Playing)),
+                    show_net_pause.run_if(in_state(GameState::Playing)),
+                    show_slow_peer.run_if(in_state(GameState::Playing)),
+                    update_forfeit_hold.run_if(in_state(GameState::Playing)),
+                    show_overtime.run_if(in_state(GameState::Playing)),
+                    restore_trail_materials
+                        .run_if(in_state(RollbackState::InRound))
+                        .run_if(state_changed::<RollbackState>),
+                    fade_trails_by_age.run_if(in_state(RollbackState::InRound)),
+                    pulse_trail_owners.run_if(in_state(GameState::Playing)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_spectate_target,
+                    move_camera.after(update_spectate_target),
+                    show_spawn_indicators,
+                    update_local_player_marker,
+                    sync_player_visuals,
+                    start_screen_shake,
+                    start_round_intro,
+                    flash_trail_eraser,
+                    highlight_killing_trail,
+                    spawn_death_bursts,
+                    animate_death_bursts,
+                    cue_boost_pads,
+                    rumble_gamepads,
+                    show_predicted_path,
+                    show_wind,
+                    (toggle_standings, update_standings).chain(),
+                    (toggle_camera_mode, overview_camera).chain(),
+                    log_state_hash,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            // the shake is added on top of wherever move_camera put the camera, and taken back off
+            // before the next frame so it never builds up
+            .add_systems(First, (remove_screen_shake, remove_round_intro).chain())
+            .add_systems(
+                PostUpdate,
+                (apply_round_intro, apply_screen_shake)
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .before(TransformSystems::Propagate),
+            );
+    }
+}
+
+// Floats are hashed by their exact bits and handles as u64, so every platform agrees
+fn checksum_transform(transform: &Transform) -> u64 {
+    FixedHasher.hash_one((
+        transform.translation.to_array().map(f32::to_bits),
+        transform.rotation.to_array().map(f32::to_bits),
+    ))
+}
+
+fn checksum_velocity(velocity: &Velocity) -> u64 {
+    FixedHasher.hash_one(velocity.to_array().map(f32::to_bits))
+}
+
+fn checksum_player(player: &Player) -> u64 {
+    FixedHasher.hash_one((
+        player.handle as u64,
+        player.fuel.to_bits(),
+        player.hovering,
+        player.braking,
+        player.dashing,
+        player.dash_cooldown,
+        player.boost,
+        player.spawn_grace,
+        player.turn_velocity.to_bits(),
+        player.grounded,
+    ))
+}
+
+fn checksum_trail_segment(segment: &TrailSegment) -> u64 {
+    FixedHasher.hash_one((
+        segment.owner as u64,
+        segment.created_at.to_bits(),
+        segment.created_frame,
+        segment.length.to_bits(),
+        segment.dashed,
+    ))
+}
+
+fn checksum_scores(scores: &Scores) -> u64 {
+    let mut scores: Vec<(u64, u32)> = scores
+        .iter()
+        .map(|(handle, score)| (*handle as u64, *score))
+        .collect();
+    scores.sort();
+    FixedHasher.hash_one(scores)
+}
+
+fn checksum_death_stack(death_stack: &DeathStack) -> u64 {
+    FixedHasher.hash_one(
+        death_stack
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|handle| *handle as u64)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn checksum_death_log(death_log: &DeathLog) -> u64 {
+    FixedHasher.hash_one(
+        death_log
+            .iter()
+            .map(|death| {
+                (
+                    death.handle as u64,
+                    death.killer as u64,
+                    death.frame,
+                    death.running_frame,
+                    death.trail_frame,
+                    death.position.to_array().map(f32::to_bits),
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn checksum_handicaps(handicaps: &Handicaps) -> u64 {
+    FixedHasher.hash_one(&handicaps.0)
+}
+
+fn checksum_continue_votes(votes: &ContinueVotes) -> u64 {
+    FixedHasher.hash_one(
+        votes
+            .iter()
+            .map(|handle| *handle as u64)
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn checksum_casual_round(casual: &CasualRound) -> u64 {
+    FixedHasher.hash_one((
+        casual.frames,
+        casual
+            .respawns
+            .iter()
+            .map(|(handle, at)| (*handle as u64, *at))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+fn checksum_modifier_votes(votes: &ModifierVotes) -> u64 {
+    let held: Vec<u64> = votes
+        .held
+        .iter()
+        .map(|held| FixedHasher.hash_one(held))
+        .collect();
+    let mut votes: Vec<(u64, u64)> = votes
+        .iter()
+        .map(|(handle, vote)| (*handle as u64, FixedHasher.hash_one(vote)))
+        .collect();
+    votes.sort();
+    FixedHasher.hash_one((votes, held))
+}
+
+fn checksum_boost_log(boost_log: &BoostLog) -> u64 {
+    FixedHasher.hash_one(
+        boost_log
+            .iter()
+            .map(|boost| (boost.handle as u64, boost.pad as u64, boost.frame))
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn checksum_eraser_log(eraser_log: &EraserLog) -> u64 {
+    FixedHasher.hash_one(
+        eraser_log
+            .iter()
+            .map(|eraser| (eraser.handle as u64, eraser.frame))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Press F8 to log a hash of the whole simulated state, and of each part of it. Peers that log
+/// different hashes for the same frame have desynced, and the parts show where.
+#[allow(clippy::too_many_arguments)]
+fn log_state_hash(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    context: MatchContext,
+    players: Query<(&Transform, &Velocity, &Player)>,
+    trails: Query<(&Transform, &TrailSegment)>,
+    scores: Res<Scores>,
+    death_stack: Res<DeathStack>,
+    frame_count: Res<FrameCount>,
+    match_clock: Res<MatchClock>,
+    rng: Res<GameRng>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+
+    // Entity order differs between peers, so sort everything by something simulated
+    let mut player_hashes: Vec<(usize, u64)> = players
+        .iter()
+        .map(|(transform, velocity, player)| {
+            let hash = FixedHasher.hash_one((
+                checksum_transform(transform),
+                checksum_velocity(velocity),
+                checksum_player(player),
+            ));
+            (player.handle, hash)
+        })
+        .collect();
+    player_hashes.sort();
+
+    let mut trail_hashes: Vec<u64> = trails
+        .iter()
+        .map(|(transform, segment)| {
+            FixedHasher.hash_one((
+                checksum_transform(transform),
+                checksum_trail_segment(segment),
+            ))
+        })
+        .collect();
+    trail_hashes.sort();
+
+    let parts = [
+        ("players", FixedHasher.hash_one(&player_hashes)),
+        ("trails", FixedHasher.hash_one(&trail_hashes)),
+        ("scores", checksum_scores(&scores)),
+        ("death_stack", checksum_death_stack(&death_stack)),
+        ("frame_count", FixedHasher.hash_one(*frame_count)),
+        ("match_clock", FixedHasher.hash_one(*match_clock)),
+        ("rng", FixedHasher.hash_one(*rng)),
+    ];
+
+    let _span = context.span().entered();
+    info!(
+        "state hash {:016x} ({} players, {} trail segments)",
+        FixedHasher.hash_one(parts),
+        player_hashes.len(),
+        trail_hashes.len()
+    );
+    for (name, hash) in parts {
+        info!("  {name}: {hash:016x}");
+    }
+    for (handle, hash) in player_hashes {
+        info!("  player {handle}: {hash:016x}");
+    }
+}
+
+/// Collects player inputs during [`ReadInputs`](`bevy_ggrs::ReadInputs`) and creates a [`LocalInputs`] resource.
+pub fn read_local_inputs(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    local_players: Res<LocalPlayers>,
+    controls: Res<ControlSettings>,
+    forfeit: Res<ForfeitHold>,
+    gamepads: Query<&Gamepad>,
+    pause_menu: Res<PauseMenu>,
+) {
+    // The keys are the menu's while it's open, Enter included, so we carry straight on without
+    // pressing anything
+    if *pause_menu != PauseMenu::Closed {
+        let idle = local_players
+            .0
+            .iter()
+            .map(|handle| (*handle, Input::default()))
+            .collect();
+        commands.insert_resource(LocalInputs::<GameConfig>(idle));
+        return;
+    }
+
+    let mut local_inputs = HashMap::new();
+
+    // Any connected gamepad works alongside the keyboard
+    let pad = |button: GamepadButton| gamepads.iter().any(|gamepad| gamepad.pressed(button));
+    let stick_x = gamepads
+        .iter()
+        .map(|gamepad| gamepad.left_stick().x)
+        .find(|x| x.abs() >= STICK_DEADZONE);
+
+    for handle in &local_players.0 {
+        let jump = keyboard_input.pressed(KeyCode::Space) || pad(GamepadButton::South);
+        let hover_key = controls.hover_key.key_code();
+        let hover = match hover_key {
+            Some(key) => keyboard_input.pressed(key) || pad(GamepadButton::RightTrigger2),
+            None => jump,
+        };
+        let left = keyboard_input.pressed(KeyCode::ArrowLeft) || pad(GamepadButton::DPadLeft);
+        let right = keyboard_input.pressed(KeyCode::ArrowRight) || pad(GamepadButton::DPadRight);
+
+        let mut input = Input::default()
+            .with_left(left)
+            .with_right(right)
+            .with_jump(jump)
+            .with_dash(keyboard_input.pressed(KeyCode::KeyZ) || pad(GamepadButton::West))
+            .with_hover(hover)
+            .with_separate_hover(hover_key.is_some())
+            .with_continue(keyboard_input.pressed(KeyCode::Enter) || pad(GamepadButton::Start))
+            .with_forfeit(forfeit.secs >= FORFEIT_HOLD_SECS)
+            // Not an arrow key, up and down move through the pause menu
+            .with_brake(keyboard_input.pressed(KeyCode::KeyX) || pad(GamepadButton::LeftTrigger2))
+            .with_pause(keyboard_input.pressed(KeyCode::KeyP) || pad(GamepadButton::Select));
+
+        // Keys and the d-pad win over the stick
+        if let Some(x) = stick_x
+            && !left
+            && !right
+        {
+            input = if controls.analog_turn {
+                input.with_analog_turn(x)
+            } else {
+                input.with_left(x < 0.0).with_right(x > 0.0)
+            };
+        }
+
+        local_inputs.insert(*handle, input);
+    }
+
+    commands.insert_resource(LocalInputs::<GameConfig>(local_inputs));
+}
+
+/// Setup sphere and lights then set rollback state to in round
+#[allow(clippy::too_many_arguments)]
+fn setup_env(
+    mut commands: Commands,
+    session: Res<Session<GameConfig>>,
+    mut scores: ResMut<Scores>,
+    hud: Res<HudSettings>,
+    physics: Res<PhysicsConfig>,
+    rng: Res<GameRng>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut ambient_light: ResMut<GlobalAmbientLight>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut next_state: ResMut<NextState<RollbackState>>,
+) {
+    let num_players = session_num_players(&session);
+
+    commands.insert_resource(MatchClock::default());
+    commands.insert_resource(DeathLog::default());
+    commands.insert_resource(EraserLog::default());
+    commands.insert_resource(BoostLog::default());
+    commands.insert_resource(NetPause::default());
+    commands.insert_resource(MatchStats {
+        kills: vec![0; num_players],
+        frames_alive: vec![0; num_players],
+    });
+    commands.insert_resource(RoundModifier::default());
+    commands.insert_resource(ModifierVotes::default());
+
+    // Reset and init scores
+    scores.clear();
+    for handle in 0..num_players {
+        scores.insert(handle, 0);
+    }
+
+    // Scoreboard text
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::FlexStart,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        children![(
+            Node {
+                align_self: AlignSelf::Center,
+                justify_content: JustifyContent::Center,
+                // wrap instead of running off screen with lots of players
+                max_width: Val::Percent(100.0),
+                ..Default::default()
+            },
+            Text::new(scoreboard_text(&scores, hud.scoreboard_order, &physics)),
+            TextFont {
+                font_size: 48.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            Scoreboard,
+            children![
+                (TextSpan::default(), MatchClockText),
+                (TextSpan::default(), VictoryText),
+            ],
+        )],
+    ));
+
+    // Trail materials, ready before anyone lays down a trail
+    commands.insert_resource(TrailMaterials(
+        (0..num_players)
+            .map(|handle| {
+                let color = slot_color(handle, num_players);
+                let dash_color = color.mix(&Color::WHITE, DASH_TRAIL_BRIGHTEN);
+                [color, dash_color].map(|color| {
+                    std::array::from_fn(|band| {
+                        materials.add(StandardMaterial {
+                            base_color: color.with_alpha(trail_band_alpha(band)),
+                            alpha_mode: trail_band_alpha_mode(band),
+                            ..default()
+                        })
+                    })
+                })
+            })
+            .collect(),
+    ));
+
+    commands.insert_resource(EraserAssets {
+        mesh: meshes.add(Sphere::new(ERASER_RADIUS)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            emissive: LinearRgba::rgb(2.0, 2.0, 2.0),
+            ..default()
+        }),
+    });
+
+    // Boost pads, placed with a copy of the rng so the simulation still draws the same numbers
+    let mut pad_rng = *rng;
+    let pad_mesh = meshes.add(Cylinder::new(BOOST_PAD_RADIUS, 0.02));
+    let pad_material = materials.add(StandardMaterial {
+        base_color: Color::srgb(1.0, 0.6, 0.0),
+        emissive: LinearRgba::rgb(3.0, 1.5, 0.0),
+        ..default()
+    });
+    for index in 0..physics.boost_pads as usize {
+        let position = pad_rng.point_on_sphere();
+        commands.spawn((
+            DespawnOnExit(GameState::Playing),
+            BoostPad { index },
+            Mesh3d(pad_mesh.clone()),
+            MeshMaterial3d(pad_material.clone()),
+            // Flat on the surface
+            Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_arc(Vec3::Y, position.normalize())),
+        ));
+    }
+
+    // Edges of the play area, when it doesn't cover the whole sphere
+    if let Some(band) = physics.arena_band() {
+        let edge_material = materials.add(StandardMaterial {
+            base_color: Color::srgb(1.0, 0.2, 0.3),
+            emissive: LinearRgba::rgb(2.0, 0.3, 0.5),
+            ..default()
+        });
+        for latitude in [band.min_latitude, band.max_latitude] {
+            // A pole isn't an edge
+            if latitude.abs() >= 90.0 {
+                continue;
+            }
+            let latitude = latitude.to_radians();
+            let ring_radius = SPHERE_RADIUS * ops::cos(latitude);
+            commands.spawn((
+                DespawnOnExit(GameState::Playing),
+                Mesh3d(meshes.add(Torus::new(
+                    ring_radius - ARENA_EDGE_WIDTH,
+                    ring_radius + ARENA_EDGE_WIDTH,
+                ))),
+                MeshMaterial3d(edge_material.clone()),
+                Transform::from_xyz(0.0, SPHERE_RADIUS * ops::sin(latitude), 0.0),
+            ));
+        }
+    }
+
+    // Brighten
+    ambient_light.brightness = 500.0;
+
+    // Sphere
+    commands.spawn((
+        DespawnOnExit(GameState::Playing),
+        Arena,
+        Mesh3d(meshes.add(Sphere::new(SPHERE_RADIUS))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgba_u8(64, 198, 255, 104),
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        })),
+        Transform {
+            translation: Vec3::ZERO,
+            ..Default::default()
+        },
+    ));
+
+    next_state.set(RollbackState::InRound);
+}
+
+/// Where a player spawns, using the hand-picked slots when they cover everyone
+fn spawn_transform(handle: usize, num_players: usize) -> Transform {
+    if num_players <= SLOT_INFO.len() {
+        // Out of the sphere for up, and another axis for forward so it's tangent to the surface
+        let (up, forward) = match handle {
+            0 => (Vec3::Y, Vec3::X),
+            1 => (Vec3::NEG_Y, Vec3::NEG_X),
+            2 => (Vec3::X, Vec3::Z),
+            3 => (Vec3::NEG_X, Vec3::NEG_Z),
+            4 => (Vec3::Z, Vec3::Y),
+            5 => (Vec3::NEG_Z, Vec3::NEG_Y),
+            _ => unreachable!(),
+        };
+
+        return Transform::from_translation(up * SPHERE_RADIUS).looking_to(forward, up);
+    }
+
+    // Spread everyone evenly over the sphere with a fibonacci lattice
+    // `ops` so these go through libm with the deterministic-math feature
+    let golden_angle = PI * (3.0 - ops::sqrt(5.0));
+    let y = 1.0 - 2.0 * (handle as f32 + 0.5) / num_players as f32;
+    let ring_radius = ops::sqrt(1.0 - y * y);
+    let theta = golden_angle * handle as f32;
+    let up = Vec3::new(
+        ops::cos(theta) * ring_radius,
+        y,
+        ops::sin(theta) * ring_radius,
+    );
+
+    // Face along the tangent plane, towards the pole furthest from us
+    let reference = if y > 0.0 { Vec3::NEG_Y } else { Vec3::Y };
+    let forward = (reference - up * reference.dot(up)).normalize();
+
+    Transform::from_translation(up * SPHERE_RADIUS).looking_to(forward, up)
+}
+
+/// Latitude of `pos` in degrees, 90 at the top pole
+fn latitude(pos: Vec3) -> f32 {
+    ops::asin(pos.normalize_or_zero().y.clamp(-1.0, 1.0)).to_degrees()
+}
+
+/// What happens to a player at `pos`, if it's outside [`PhysicsConfig::arena_band`]
+fn outside_arena(pos: Vec3, physics: &PhysicsConfig) -> Option<ArenaEdge> {
+    let band = physics.arena_band()?;
+    let latitude = latitude(pos);
+    (latitude < band.min_latitude || latitude > band.max_latitude).then_some(band.edge)
+}
+
+/// Moves a spawn into [`PhysicsConfig::arena_band`], squeezing the whole sphere's latitudes into
+/// the band's so spawns stay as spread out as they can
+fn fit_to_arena(mut spawn: Transform, physics: &PhysicsConfig) -> Transform {
+    let Some(band) = physics.arena_band() else {
+        return spawn;
+    };
+
+    let latitude = latitude(spawn.translation);
+    let target =
+        band.min_latitude + (latitude + 90.0) / 180.0 * (band.max_latitude - band.min_latitude);
+    // Tilt along the meridian, or along the spawn's own heading at a pole where there isn't one
+    let axis = spawn
+        .translation
+        .cross(Vec3::Y)
+        .try_normalize()
+        .unwrap_or(spawn.right().as_vec3());
+    spawn.rotate_around(
+        Vec3::ZERO,
+        Quat::from_axis_angle(axis, (target - latitude).to_radians()),
+    );
+    spawn
+}
+
+/// Puts a player who went over an [`ArenaEdge::Bounce`] edge back where they were `before`, with
+/// the north-south part of their heading flipped so they head back in
+fn bounce_off_arena_edge(transform: &mut Transform, before: Transform) {
+    let up = before.translation.normalize();
+    let north = (Vec3::Y - up * up.y).normalize_or_zero();
+    let forward = before.forward().as_vec3();
+    let reflected = forward - 2.0 * forward.dot(north) * north;
+    *transform = before.looking_to(reflected, up);
+}
+
+/// Whether the last two players get the [`PhysicsConfig::last_stand_speed`] boost
+fn is_last_stand(alive: usize, num_players: usize, physics: &PhysicsConfig) -> bool {
+    physics.last_stand_speed != 1.0 && alive == 2 && num_players > 2
+}
+
+/// Number of players in the current session
+fn session_num_players(session: &Session<GameConfig>) -> usize {
+    match session {
+        Session::SyncTest(s) => s.num_players(),
+        Session::P2P(s) => s.num_players(),
+        Session::Spectator(s) => s.num_players(),
+    }
+}
+
+/// make sure no leftover players or trails, then spawn in players
+#[allow(clippy::too_many_arguments)]
+fn spawn_players(
+    mut commands: Commands,
+    session: Res<Session<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+    players: Query<Entity, With<Player>>,
+    trails: Query<Entity, With<TrailSegment>>,
+    erasers: Query<Entity, With<TrailEraser>>,
+    mut death_stack: ResMut<DeathStack>,
+    mut casual: ResMut<CasualRound>,
+    mut round_scored: ResMut<RoundScored>,
+    mut countdown: ResMut<RoundCountdown>,
+    mut rng: ResMut<GameRng>,
+) {
+    for player in players {
+        commands.entity(player).try_despawn();
+    }
+
+    for trail in trails {
+        commands.entity(trail).try_despawn();
+    }
+
+    for eraser in erasers {
+        commands.entity(eraser).try_despawn();
+    }
+
+    death_stack.clear();
+    *casual = CasualRound::default();
+    **round_scored = false;
+    **countdown = physics.round_countdown_frames;
+
+    let num_players = session_num_players(&session);
+
+    let slots = spawn_slots(num_players, &physics, &mut rng);
+
+    if physics.wind_strength != 0.0 {
+        commands.insert_resource(Wind {
+            seed: rng.next_u64() as u16,
+        });
+    }
+
+    for handle in 0..num_players {
+        // Entities which will be rolled back can be created just like any other...
+        // Which handles are ours comes from the session, see `LocalPlayers` and
+        // `update_local_player_marker`
+        spawn_player(
+            &mut commands,
+            handle,
+            fit_to_arena(spawn_transform(slots[handle], num_players), &physics),
+            &physics,
+        );
+    }
+}
+
+/// Which spawn slot each handle gets this round, shuffled when
+/// [`shuffle_spawns`](PhysicsConfig::shuffle_spawns) is on. Every handle is shuffled whether or
+/// not it made it to the end of the last round, so the number of draws never depends on who's
+/// alive.
+fn spawn_slots(num_players: usize, physics: &PhysicsConfig, rng: &mut GameRng) -> Vec<usize> {
+    let mut slots: Vec<usize> = (0..num_players).collect();
+    if physics.shuffle_spawns {
+        rng.shuffle(&mut slots);
+    }
+    slots
+}
+
+/// Catches anything leaking from one round into the next in debug builds. Runs after
+/// [`spawn_players`]' commands are applied, so the new round should have exactly one player per
+/// handle, no trails or erasers, nobody dead yet and a score for every handle.
+#[cfg(debug_assertions)]
+fn check_round_start(
+    session: Res<Session<GameConfig>>,
+    scores: Res<Scores>,
+    death_stack: Res<DeathStack>,
+    players: Query<&Player>,
+    trails: Query<(), With<TrailSegment>>,
+    erasers: Query<(), With<TrailEraser>>,
+) {
+    let num_players = session_num_players(&session);
+
+    let mut handles: Vec<usize> = players.iter().map(|p| p.handle).collect();
+    handles.sort();
+    assert_eq!(
+        handles,
+        (0..num_players).collect::<Vec<_>>(),
+        "round started with the wrong players"
+    );
+    assert_eq!(
+        trails.iter().count(),
+        0,
+        "round started with leftover trails"
+    );
+    assert_eq!(
+        erasers.iter().count(),
+        0,
+        "round started with a leftover eraser"
+    );
+    assert!(
+        death_stack.is_empty(),
+        "round started with deaths from the last one: {:?}",
+        **death_stack
+    );
+
+    let mut scored: Vec<usize> = scores.keys().copied().collect();
+    scored.sort();
+    assert_eq!(
+        scored,
+        (0..num_players).collect::<Vec<_>>(),
+        "round started without a score for every player"
+    );
+}
+
+/// Spawns a fresh player, at the start of a round or when they respawn. Nothing carries over
+/// from a previous life: dash timers only tick in [`move_player`], which doesn't run between
+/// rounds, so everyone starts with their dash ready instead of whatever was left paused.
+fn spawn_player(commands: &mut Commands, handle: usize, spawn: Transform, physics: &PhysicsConfig) {
+    commands
+        .spawn((
+            DespawnOnExit(GameState::Playing),
+            spawn,
+            Player {
+                handle,
+                fuel: 100.0,
+                hovering: false,
+                braking: false,
+                dashing: 0,
+                dash_cooldown: 0,
+                boost: 0,
+                spawn_grace: physics.spawn_grace_frames,
+                turn_velocity: 0.0,
+                grounded: true,
+                last_trail_pos: spawn.translation,
+                last_trail: None,
+            },
+            Velocity::default(),
+            // The model is on a separate PlayerVisual, see sync_player_visuals
+        ))
+        .add_rollback();
+}
+
+/// Of the [`RESPAWN_CANDIDATES`], the one furthest from everything in `occupied`. Ties go to the
+/// first candidate, so every peer picks the same spot.
+fn safe_spawn(occupied: &[Vec3], physics: &PhysicsConfig) -> Transform {
+    let clearance = |spawn: &Transform| {
+        occupied
+            .iter()
+            .map(|pos| pos.distance_squared(spawn.translation))
+            .fold(f32::MAX, f32::min)
+    };
+
+    let mut best = fit_to_arena(spawn_transform(0, RESPAWN_CANDIDATES), physics);
+    for slot in 1..RESPAWN_CANDIDATES {
+        let spawn = fit_to_arena(spawn_transform(slot, RESPAWN_CANDIDATES), physics);
+        if clearance(&spawn) > clearance(&best) {
+            best = spawn;
+        }
+    }
+    best
+}
+
+// Example system, manipulating a resource, will be added to the rollback schedule.
+// Increases the frame count by 1 every update step. If loading and saving resources works correctly,
+// you should see this resource rolling back, counting back up and finally increasing by 1 every update step
+#[allow(dead_code)]
+fn increase_frame_system(mut frame_count: ResMut<FrameCount>) {
+    frame_count.frame += 1;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_player(
+    mut query: Query<(&mut Transform, &mut Velocity, &mut Player), With<Player>>,
+    inputs: Res<PlayerInputs<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+    modifier: Res<RoundModifier>,
+    session: Res<Session<GameConfig>>,
+    clock: Res<MatchClock>,
+    wind: Res<Wind>,
+    handicaps: Res<Handicaps>,
+    mut countdown: ResMut<RoundCountdown>,
+    // Thanks to RollbackTimePlugin, this is rollback safe
+    time: Res<Time>,
+) {
+    if **countdown > 0 {
+        // Everyone stays put until the countdown's over
+        **countdown -= 1;
+        return;
+    }
+
+    let speed_multiplier =
+        if is_last_stand(query.iter().len(), session_num_players(&session), &physics) {
+            physics.last_stand_speed
+        } else {
+            1.0
+        };
+
+    let step = MoveStep {
+        physics: &physics,
+        modifier: *modifier,
+        clock: *clock,
+        wind: *wind,
+        speed_multiplier,
+        dt: time.delta_secs(),
+    };
+    for (mut transform, mut vel, mut player) in &mut query {
+        let input = inputs[player.handle].0;
+        let handicap = handicaps.get(player.handle);
+        step_player(
+            &mut transform,
+            &mut vel,
+            &mut player,
+            input,
+            handicap,
+            &step,
+        );
+    }
+}
+
+/// Everything [`step_player`] needs that's the same for every player on a frame
+struct MoveStep<'a> {
+    physics: &'a PhysicsConfig,
+    modifier: RoundModifier,
+    clock: MatchClock,
+    wind: Wind,
+    /// From [`is_last_stand`]
+    speed_multiplier: f32,
+    dt: f32,
+}
+
+/// Moves one player through one frame of [`move_player`]. Only touches what it's given, so the
+/// physics can be stepped outside of a session.
+fn step_player(
+    transform: &mut Transform,
+    vel: &mut Velocity,
+    player: &mut Player,
+    input: Input,
+    handicap: Handicap,
+    step: &MoveStep,
+) {
+    let MoveStep {
+        physics,
+        modifier,
+        dt,
+        ..
+    } = *step;
+    let left = input.is_left();
+    let right = input.is_right();
+    let jump = input.is_jump();
+    let dash = input.is_dash();
+    let hover = input.is_hover();
+    let is_grounded = player.update_grounded(transform.translation, vel.y);
+
+    player.braking = input.is_brake();
+
+    // Start dashing if dash was pressed, counted in frames so there's no float time to drift
+    player.dash_cooldown = player.dash_cooldown.saturating_sub(1);
+    player.boost = player.boost.saturating_sub(1);
+    let dash_cost = physics.dash_cost;
+    let can_afford = (!dash_cost.uses_cooldown() || player.dash_cooldown == 0)
+        && (!dash_cost.uses_fuel() || player.fuel >= physics.dash_fuel_cost);
+    if dash && player.dashing == 0 && can_afford && is_grounded {
+        player.dashing = handicap.dash_frames();
+        if dash_cost.uses_cooldown() {
+            player.dash_cooldown = DASH_COOLDOWN_FRAMES;
+        }
+        if dash_cost.uses_fuel() {
+            player.fuel -= physics.dash_fuel_cost;
+        }
+    }
+    player.dashing = player.dashing.saturating_sub(1);
+
+    if jump && is_grounded {
+        vel.y = JUMP_VELOCITY;
+
+        // Jumping ends dash and immediately makes it available again
+        player.dashing = 0;
+        player.dash_cooldown = 0;
+    }
+
+    let delta_grav = modifier.gravity() * dt;
+    // With its own key hover starts any time on the way down, as long as there's fuel for it.
+    // Otherwise it's jump held through the top of the jump, since holding jump to jump
+    // shouldn't hover straight away.
+    let can_start_hover = if input.is_separate_hover() {
+        player.fuel > 0.0
+    } else {
+        vel.y.is_sign_positive()
+    };
+    // Would start to fall on this update, or already falling, if hover is held, start hovering
+    if hover
+        && can_start_hover
+        && (vel.y + delta_grav).is_sign_negative()
+        && !is_grounded
+        && !player.hovering
+    {
+        player.hovering = true;
+    }
+
+    // Decrement fuel while hovering
+    if player.hovering && hover {
+        player.fuel -= FUEL_USAGE * dt;
+    }
+
+    // Stop hover if hover is released or out of fuel
+    if player.hovering && (!hover || player.fuel <= 0.0) {
+        player.hovering = false;
+    }
+
+    // Apply Gravity if in air and not hovering
+    if !player.hovering && (!is_grounded || vel.y != 0.0) {
+        vel.y += delta_grav;
+    } else {
+        vel.y = 0.0;
+    }
+
+    // We turn around the local Y axis (the alien's "up")
+    let turn_speed = TURN_SPEED * input.turn_strength();
+    let mut target_turn = 0.0;
+    if left {
+        target_turn += PI * turn_speed;
+    }
+
+    if right {
+        target_turn -= PI * turn_speed;
+    }
+
+    if physics.drift && player.dashing != 0 {
+        // At dash speed turning has momentum, so it's slow to start and keeps going after
+        let catch_up = (physics.drift_grip * dt).min(1.0);
+        player.turn_velocity += (target_turn - player.turn_velocity) * catch_up;
+    } else {
+        player.turn_velocity = target_turn;
+    }
+
+    let before = *transform;
+    let speed_multiplier = step.speed_multiplier * handicap.speed();
+    let move_amount = ground_speed(player, physics, modifier, speed_multiplier) * dt;
+    move_on_sphere(transform, player.turn_velocity * dt, move_amount);
+
+    if physics.wind_strength != 0.0 {
+        // Carried along, still facing the same way relative to the ground
+        let push = wind_at(transform.translation, &step.wind, &step.clock, physics) * dt;
+        let carry =
+            Quat::from_scaled_axis(transform.translation.normalize().cross(push) / SPHERE_RADIUS);
+        transform.translation = carry * transform.translation;
+        transform.rotation = (carry * transform.rotation).normalize();
+    }
+    if outside_arena(transform.translation, physics) == Some(ArenaEdge::Bounce) {
+        bounce_off_arena_edge(transform, before);
+    }
+    let new_up = transform.translation.normalize();
+
+    // Apply velocity along the normal (away from center)
+    transform.translation += new_up * vel.y * dt;
+
+    // Increment fuel while grounded
+    let is_grounded = player.update_grounded(transform.translation, vel.y);
+    if is_grounded && player.fuel <= 100.0 {
+        player.fuel += FUEL_REGEN * handicap.fuel_regen() * dt;
+    }
+
+    // Snap player to sphere radius if they're below
+    if transform.translation.length_squared() < SPHERE_RADIUS_SQ {
+        transform.translation = new_up * SPHERE_RADIUS;
+        vel.y = 0.0;
+    }
+
+    // Keep them from leaving the play area, and start them falling right away
+    let max_radius = SPHERE_RADIUS + physics.max_altitude.max(0.0);
+    if transform.translation.length_squared() > max_radius * max_radius {
+        transform.translation = new_up * max_radius;
+        vel.y = vel.y.min(0.0);
+    }
+}
+
+/// Wind velocity at a point, along the surface. Bands of wind flow around a slowly turning axis,
+/// alternating in strength with latitude. Only uses the simulation's clock and `ops` math, so
+/// every peer gets the same push.
+fn wind_at(pos: Vec3, wind: &Wind, clock: &MatchClock, physics: &PhysicsConfig) -> Vec3 {
+    let t = wind.seed as f32 / u16::MAX as f32 * 2.0 * PI + clock.frames as f32 / FPS as f32 * 0.1;
+    let axis = Vec3::new(ops::cos(t), 0.5, ops::sin(t)).normalize();
+    let up = pos.normalize_or_zero();
+    let flow = axis.cross(up);
+    let bands = 0.5 + 0.5 * ops::sin(3.0 * up.dot(axis) * PI + t);
+    flow * bands * physics.wind_strength
+}
+
+/// How fast a player moves over the surface, scaled by `multiplier` for the [`is_last_stand`]
+/// boost and handicaps. Never below [`PhysicsConfig::min_move_speed`], whatever slowed them.
+fn ground_speed(
+    player: &Player,
+    physics: &PhysicsConfig,
+    modifier: RoundModifier,
+    multiplier: f32,
+) -> f32 {
+    let speed = if player.dashing == 0 {
+        if player.braking {
+            MOVE_SPEED * physics.brake_factor
+        } else {
+            MOVE_SPEED
+        }
+    } else {
+        let dash_speed = match physics.dash_distance {
+            // `dashing` is counted down on the frame it's set, so a full dash moves at this speed
+            // for one frame less than its length
+            Some(distance) => distance / ((DASH_LENGTH_FRAMES - 1) as f32 / FPS as f32),
+            None => DASH_SPEED_MULTIPLIER * MOVE_SPEED,
+        };
+        dash_speed * modifier.dash_boost()
+    };
+
+    let speed = if player.boost > 0 {
+        speed * physics.boost_pad_speed
+    } else {
+        speed
+    };
+    (speed * multiplier).max(physics.min_move_speed)
+}
+
+/// Moves `distance` forward over the sphere while turning `turn` radians. Shared by
+/// [`move_player`] and [`show_predicted_path`] so the prediction matches.
+fn move_on_sphere(transform: &mut Transform, turn: f32, distance: f32) {
+    // The position vector IS the "up" vector since the sphere is centered at (0,0,0)
+    let current_pos = transform.translation;
+    let up = transform.up().as_vec3();
+    let forward = transform.forward().as_vec3();
+
+    // THE MATH:
+    // To move forward on a sphere, we rotate the POSITION vector
+    // around an axis that is perpendicular to both UP and FORWARD.
+    let axis = transform.right().as_vec3(); // This is the "side-to-side" axis
+    let angle = distance / SPHERE_RADIUS; // Angle in radians
+
+    // Turning is a rotation around UP, which also goes through the center. Doing it as one
+    // rotation instead of turning then moving traces the exact arc for this frame, so the
+    // path over the sphere doesn't depend on how long frames are.
+    let rotation_delta = Quat::from_scaled_axis(up * turn - axis * angle);
+    let new_pos = rotation_delta * current_pos;
+    let new_up = new_pos.normalize();
+
+    // Apply new position
+    transform.translation = new_pos;
+
+    // Re-orient the player to stand upright on the new position
+    // Calculate a new forward that is tangent to the sphere
+    let new_forward = rotation_delta * forward;
+
+    transform.look_at(new_pos + new_forward, new_up);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn manage_trail(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    trail_materials: Res<TrailMaterials>,
+    physics: Res<PhysicsConfig>,
+    modifier: Res<RoundModifier>,
+    players: Query<(&mut Transform, &mut Player), With<Player>>,
+    frame: Res<RollbackFrameCount>,
+    pause: Res<NetPause>,
+    time: Res<Time>,
+) {
+    let spawn_dist = physics.trail_spawn_dist();
+    let trail_radius = modifier.trail_radius();
+
+    for (transform, mut player) in players {
+        // Calculate distance since last segment
+        let dist = transform.translation.distance(player.last_trail_pos);
+
+        if dist > spawn_dist {
+            // Calculate the midpoint between current and last position
+            let midpoint = ((transform.translation + player.last_trail_pos) / 2.0)
+                + (trail_radius * transform.up());
+
+            // Direction from last to current
+            let direction = (transform.translation - player.last_trail_pos).normalize();
+
+            // Create a rotation that points the Cylinder's Y-axis (top)
+            // toward the movement direction
+            let rotation = Quat::from_rotation_arc(Vec3::Y, direction);
+
+            let dashed = player.dashing != 0;
+            let width = if dashed {
+                trail_radius * DASH_TRAIL_WIDTH
+            } else {
+                trail_radius
+            };
+
+            let last_spawned = commands
+                .spawn((
+                    DespawnOnExit(GameState::Playing),
+                    // As long as it collides, but never shorter than it is wide so sparse
+                    // trails still look solid
+                    Mesh3d(meshes.add(Cylinder::new(width, trail_radius.max(dist)))),
+                    MeshMaterial3d(trail_materials[player.handle][dashed as usize][0].clone()),
+                    Transform {
+                        translation: midpoint,
+                        rotation,
+                        ..default()
+                    },
+                    TrailSegment {
+                        owner: player.handle,
+                        created_at: time.elapsed_secs_f64(),
+                        created_frame: pause.running_frame(*frame),
+                        length: dist,
+                        dashed,
+                    },
+                ))
+                .add_rollback()
+                .id();
+
+            // Update the last spawn position to current position
+            player.last_trail_pos = transform.translation;
+            player.last_trail = Some(last_spawned);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_collisions(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Transform, &mut Player), With<Player>>,
+    trails: Query<(&Transform, &TrailSegment), With<TrailSegment>>,
+    mut death_stack: ResMut<DeathStack>,
+    mut death_log: ResMut<DeathLog>,
+    physics: Res<PhysicsConfig>,
+    modifier: Res<RoundModifier>,
+    inputs: Res<PlayerInputs<GameConfig>>,
+    frame: Res<RollbackFrameCount>,
+    pause: Res<NetPause>,
+) {
+    let deaths = find_deaths(
+        &players.iter().collect::<Vec<_>>(),
+        &trails.iter().collect::<Vec<_>>(),
+        &inputs,
+        &physics,
+        *modifier,
+        i32::from(*frame),
+        pause.running_frame(*frame),
+    );
+
+    // Counted down after the check rather than in move_player, so a grace of N frames covers
+    // the first N frames after spawning
+    for (_, _, mut player) in &mut players {
+        player.spawn_grace = player.spawn_grace.saturating_sub(1);
+    }
+
+    if deaths.is_empty() {
+        return;
+    }
+
+    for (entity, death) in &deaths {
+        commands.entity(*entity).try_despawn();
+        death_log.0.push(*death);
+    }
+    death_stack.push(deaths.iter().map(|(_, death)| death.handle).collect());
+}
+
+/// Everyone who dies on `frame` and why, for [`check_collisions`] to apply. Sorted by handle so
+/// every peer records the same deaths in the same order, whatever order the queries gave.
+/// Trails age by `running_frame`, see [`NetPause::running_frame`].
+fn find_deaths(
+    players: &[(Entity, &Transform, &Player)],
+    trails: &[(&Transform, &TrailSegment)],
+    inputs: &[(Input, InputStatus)],
+    physics: &PhysicsConfig,
+    modifier: RoundModifier,
+    frame: i32,
+    running_frame: i32,
+) -> Vec<(Entity, Death)> {
+    let mut deaths: Vec<(Entity, Death)> = Vec::new();
+    let trail_radius = modifier.trail_radius();
+
+    for &(entity, player_trans, player) in players {
+        if inputs[player.handle].0.is_forfeit() {
+            // Counts as running into their own trail
+            deaths.push((
+                entity,
+                Death {
+                    handle: player.handle,
+                    killer: player.handle,
+                    frame,
+                    running_frame,
+                    trail_frame: None,
+                    position: player_trans.translation,
+                },
+            ));
+            continue;
+        }
+
+        if player.spawn_grace > 0 {
+            // Still protected after spawning
+            continue;
+        }
+
+        if outside_arena(player_trans.translation, physics) == Some(ArenaEdge::Kill) {
+            // Went over the edge, also counts as running into their own trail
+            deaths.push((
+                entity,
+                Death {
+                    handle: player.handle,
+                    killer: player.handle,
+                    frame,
+                    running_frame,
+                    trail_frame: None,
+                    position: player_trans.translation,
+                },
+            ));
+            continue;
+        }
+
+        let mut hit: Option<(f32, &TrailSegment)> = None;
+        for &(trail_transform, segment) in trails {
+            if segment.owner == player.handle
+                && running_frame - segment.created_frame < MIN_TRAIL_LIFE_FRAMES
+            {
+                // Don't collide with own most recently spawned segment
+                continue;
+            }
+
+            let p = player_trans.translation;
+            let b = trail_transform.translation;
+
+            // We need the direction the trail is pointing to find the ends
+            // Since we used Quat::from_rotation_arc(Vec3::Y, direction),
+            // the trail's local Y axis is its "length"
+            let trail_dir = trail_transform.up();
+
+            let start = b - trail_dir * segment.length / 2.0;
+            let end = b + trail_dir * segment.length / 2.0;
+
+            // Calculate distance from point P to segment [start, end]
+            let distance = dist_to_segment(p, start, end);
+
+            let radius = trail_radius * segment.thickness(running_frame, physics);
+            if radius <= 0.0 || distance >= (radius + PLAYER_RADIUS) {
+                continue;
+            }
+
+            // Touching more than one, blame the closest. Ties go to the lowest owner and oldest
+            // segment so the query order never decides who gets the kill.
+            let key = |segment: &TrailSegment| (segment.owner, segment.created_frame);
+            let closer = hit.is_none_or(|(hit_distance, hit_segment)| {
+                distance
+                    .total_cmp(&hit_distance)
+                    .then(key(segment).cmp(&key(hit_segment)))
+                    .is_lt()
+            });
+            if closer {
+                hit = Some((distance, segment));
+            }
+        }
+
+        if let Some((_, segment)) = hit {
+            deaths.push((
+                entity,
+                Death {
+                    handle: player.handle,
+                    killer: segment.owner,
+                    frame,
+                    running_frame,
+                    trail_frame: Some(segment.created_frame),
+                    position: player_trans.translation,
+                },
+            ));
+        }
+    }
+
+    if physics.player_collision != PlayerCollision::Off {
+        // Sorted so every pair is checked the same way round on every peer
+        let mut alive: Vec<(Entity, Vec3, &Player)> = players
+            .iter()
+            .filter(|(_, _, player)| {
+                player.spawn_grace == 0 && !deaths.iter().any(|(_, d)| d.handle == player.handle)
+            })
+            .map(|&(entity, transform, player)| (entity, transform.translation, player))
+            .collect();
+        alive.sort_by_key(|(_, _, player)| player.handle);
+
+        // (entity, handle, killer, position)
+        let mut crashed = Vec::new();
+        for (i, (entity_a, pos_a, a)) in alive.iter().enumerate() {
+            for (entity_b, pos_b, b) in &alive[i + 1..] {
+                if pos_a.distance(*pos_b) >= PLAYER_RADIUS * 2.0 {
+                    continue;
+                }
+
+                let a_dashing = a.dashing != 0;
+                let b_dashing = b.dashing != 0;
+                let (a_dies, b_dies) = match physics.player_collision {
+                    PlayerCollision::DashWins if a_dashing != b_dashing => (b_dashing, a_dashing),
+                    _ => (true, true),
+                };
+                if a_dies {
+                    crashed.push((*entity_a, a.handle, b.handle, *pos_a));
+                }
+                if b_dies {
+                    crashed.push((*entity_b, b.handle, a.handle, *pos_b));
+                }
+            }
+        }
+
+        for (entity, handle, killer, position) in crashed {
+            if deaths.iter().any(|(_, d)| d.handle == handle) {
+                // Already crashed into someone else this frame
+                continue;
+            }
+            deaths.push((
+                entity,
+                Death {
+                    handle,
+                    killer,
+                    frame,
+                    running_frame,
+                    trail_frame: None,
+                    position,
+                },
+            ));
+        }
+    }
+
+    deaths.sort_by_key(|(_, death)| death.handle);
+    deaths
+}
+
+/// Removes the trails of players that died at least
+/// [`dead_trail_decay_frames`](PhysicsConfig::dead_trail_decay_frames) ago
+fn decay_dead_trails(
+    mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    frame: Res<RollbackFrameCount>,
+    pause: Res<NetPause>,
+    death_log: Res<DeathLog>,
+    players: Query<&Player>,
+    trails: Query<(Entity, &TrailSegment)>,
+) {
+    let Some(decay_frames) = physics.dead_trail_decay_frames else {
+        return;
+    };
+    let running_frame = pause.running_frame(*frame);
+
+    for (entity, segment) in &trails {
+        if players.iter().any(|p| p.handle == segment.owner) {
+            continue;
+        }
+
+        if dead_trail_decayed(segment.owner, &death_log, running_frame, decay_frames) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Whether `owner`, who isn't alive, died at least `decay_frames` before `running_frame`, see
+/// [`NetPause::running_frame`]
+fn dead_trail_decayed(
+    owner: usize,
+    death_log: &DeathLog,
+    running_frame: i32,
+    decay_frames: u32,
+) -> bool {
+    // The log covers the whole match, but the owner isn't alive so their latest death is from
+    // this round
+    death_log
+        .iter()
+        .rev()
+        .find(|death| death.handle == owner)
+        .is_some_and(|death| running_frame - death.running_frame >= decay_frames as i32)
+}
+
+/// Thins trail segments as they age and clears them out once they're gone, when
+/// [`PhysicsConfig::trail_shrink_frames`] is set. The mesh is scaled to match the radius
+/// [`check_collisions`] uses.
+fn shrink_trails(
+    mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    frame: Res<RollbackFrameCount>,
+    pause: Res<NetPause>,
+    mut trails: Query<(Entity, &mut Transform, &TrailSegment)>,
+) {
+    if physics.trail_shrink_frames.is_none() {
+        return;
+    }
+    let running_frame = pause.running_frame(*frame);
+
+    for (entity, mut transform, segment) in &mut trails {
+        let thickness = segment.thickness(running_frame, &physics);
+        if thickness == 0.0 {
+            commands.entity(entity).try_despawn();
+        } else {
+            // The cylinder's length is along Y
+            transform.scale = Vec3::new(thickness, 1.0, thickness);
+        }
+    }
+}
+
+/// Now and then drops a [`TrailEraser`] somewhere random, if there isn't one already
+fn spawn_trail_eraser(
+    mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    assets: Res<EraserAssets>,
+    mut rng: ResMut<GameRng>,
+    erasers: Query<(), With<TrailEraser>>,
+    time: Res<Time>,
+) {
+    if physics.eraser_spawn_chance <= 0.0 || !erasers.is_empty() {
+        return;
+    }
+
+    if !rng.chance(physics.eraser_spawn_chance * time.delta_secs()) {
+        return;
+    }
+
+    let position = rng.point_on_sphere();
+    commands
+        .spawn((
+            DespawnOnExit(GameState::Playing),
+            TrailEraser,
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(assets.material.clone()),
+            Transform::from_translation(position),
+        ))
+        .add_rollback();
+}
+
+/// The lowest handle touching an eraser picks it up, erasing every trail around them
+fn collect_trail_eraser(
+    mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    frame: Res<RollbackFrameCount>,
+    mut eraser_log: ResMut<EraserLog>,
+    erasers: Query<(Entity, &Transform), With<TrailEraser>>,
+    players: Query<(&Transform, &Player)>,
+    trails: Query<(Entity, &Transform), With<TrailSegment>>,
+) {
+    for (eraser, eraser_transform) in &erasers {
+        // Query order isn't the same on every peer, so settle ties by handle
+        let Some((collector, handle)) = players
+            .iter()
+            .filter(|(transform, _)| {
+                transform.translation.distance(eraser_transform.translation)
+                    < PLAYER_RADIUS + ERASER_RADIUS
+            })
+            .map(|(transform, player)| (transform.translation, player.handle))
+            .min_by_key(|(_, handle)| *handle)
+        else {
+            continue;
+        };
+
+        commands.entity(eraser).despawn();
+        eraser_log.0.push(EraserUse {
+            handle,
+            frame: i32::from(*frame),
+        });
+
+        // Which segments go only depends on positions, so every peer erases the same ones
+        for (trail, trail_transform) in &trails {
+            if trail_transform.translation.distance(collector) < physics.eraser_radius {
+                commands.entity(trail).despawn();
+            }
+        }
+    }
+}
+
+/// Boosts anyone touching a [`BoostPad`]. Only players that aren't already boosted count, so
+/// each run over a pad is one use.
+fn hit_boost_pads(
+    physics: Res<PhysicsConfig>,
+    frame: Res<RollbackFrameCount>,
+    mut boost_log: ResMut<BoostLog>,
+    pads: Query<(&Transform, &BoostPad)>,
+    mut players: Query<(&Transform, &mut Player)>,
+) {
+    for (transform, mut player) in &mut players {
+        if player.boost > 0 {
+            continue;
+        }
+
+        // Lowest index if pads overlap, so every peer logs the same one
+        let Some(pad) = pads
+            .iter()
+            .filter(|(pad_transform, _)| {
+                pad_transform.translation.distance(transform.translation)
+                    < PLAYER_RADIUS + BOOST_PAD_RADIUS
+            })
+            .map(|(_, pad)| pad.index)
+            .min()
+        else {
+            continue;
+        };
+
+        player.boost = physics.boost_pad_frames;
+        boost_log.0.push(BoostUse {
+            handle: player.handle,
+            pad,
+            frame: i32::from(*frame),
+        });
+    }
+}
+
+fn dist_to_segment(p: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let v = b - a;
+    let w = p - a;
+    let c1 = w.dot(v);
+    if c1 <= 0.0 {
+        return p.distance(a);
+    }
+    let c2 = v.dot(v);
+    if c2 <= c1 {
+        return p.distance(b);
+    }
+    let b2 = c1 / c2;
+    let pb = a + v * b2;
+    p.distance(pb)
+}
+
+/// Scores kills and brings players back in a [`PhysicsConfig::casual`] round
+#[allow(clippy::too_many_arguments)]
+fn run_casual_round(
+    mut commands: Commands,
+    physics: Res<PhysicsConfig>,
+    frame: Res<RollbackFrameCount>,
+    death_log: Res<DeathLog>,
+    mut casual: ResMut<CasualRound>,
+    mut scores: ResMut<Scores>,
+    players: Query<&Transform, With<Player>>,
+    trails: Query<&Transform, With<TrailSegment>>,
+) {
+    let Some(CasualRules {
+        respawn_delay_frames,
+        ..
+    }) = physics.casual
+    else {
+        return;
+    };
+    casual.frames += 1;
+
+    let frame = i32::from(*frame);
+    for death in death_log.iter().filter(|death| death.frame == frame) {
+        // Crashing into your own trail isn't a kill
+        if death.killer != death.handle
+            && let Some(score) = scores.get_mut(&death.killer)
+        {
+            *score += 1;
+        }
+        let at = casual.frames + respawn_delay_frames;
+        casual.respawns.push((death.handle, at));
+    }
+
+    let mut due: Vec<usize> = casual
+        .respawns
+        .iter()
+        .filter(|(_, at)| *at <= casual.frames)
+        .map(|(handle, _)| *handle)
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+    due.sort();
+    let now = casual.frames;
+    casual.respawns.retain(|(_, at)| *at > now);
+
+    let mut occupied: Vec<Vec3> = players
+        .iter()
+        .chain(&trails)
+        .map(|transform| transform.translation)
+        .collect();
+    for handle in due {
+        let spawn = safe_spawn(&occupied, &physics);
+        // Keep anyone coming back on the same frame apart too
+        occupied.push(spawn.translation);
+        spawn_player(&mut commands, handle, spawn, &physics);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_round_end(
+    session: Res<Session<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+    casual: Res<CasualRound>,
+    players: Query<&Player, With<Player>>,
+    mut scores: ResMut<Scores>,
+    mut round_scored: ResMut<RoundScored>,
+    death_stack: Res<DeathStack>,
+    mut next_state: ResMut<NextState<RollbackState>>,
+) {
+    if **round_scored {
+        return;
+    }
+
+    if let Some(rules) = physics.casual {
+        // Kills were scored as they happened
+        if casual.frames >= rules.round_frames {
+            **round_scored = true;
+            next_state.set(RollbackState::RoundEnd);
+        }
+        return;
+    }
+
+    let num_players = session_num_players(&session);
+
+    let num_players_remaining = players.count();
+    // Practicing alone, the round goes on until they crash
+    let round_over = if num_players == 1 {
+        num_players_remaining == 0
+    } else {
+        num_players_remaining <= 1
+    };
+
+    if round_over {
+        // 0 or 1 player left, game over and distribute scores
+        **round_scored = true;
+
+        // Who was trailing going into the round, before this round's points shift things
+        let comeback = (physics.comeback_bonus > 0)
+            .then(|| comeback_candidate(&scores))
+            .flatten()
+            .map(|handle| (handle, scores[&handle]));
+
+        let mut add_score = num_players as u32 - 1;
+        if let Ok(last_alive) = players.single() {
+            *scores.get_mut(&last_alive.handle).unwrap() += add_score;
+            add_score -= 1;
+        }
+
+        // Players that died on the same frame tie, and the places they took up are skipped.
+        // When nobody is left that includes first place, so a double KO is a shared win.
+        for group in death_stack.iter().rev() {
+            for handle in group {
+                *scores.get_mut(handle).unwrap() += add_score;
+            }
+            add_score = add_score.saturating_sub(group.len() as u32);
+        }
+
+        // A finish in the top half of the placements gets them a little extra
+        if let Some((handle, before)) = comeback {
+            let earned = scores[&handle] - before;
+            if earned > 0 && earned * 2 >= num_players as u32 - 1 {
+                *scores.get_mut(&handle).unwrap() += physics.comeback_bonus;
+            }
+        }
+
+        next_state.set(RollbackState::RoundEnd);
+    }
+}
+
+fn update_scoreboard(
+    mut scoreboard: Single<&mut Text, With<Scoreboard>>,
+    scores: Res<Scores>,
+    hud: Res<HudSettings>,
+    physics: Res<PhysicsConfig>,
+) {
+    // Rollbacks restore Scores, which counts as a change even when nothing moved, so compare too
+    if !scores.is_changed() && !hud.is_changed() {
+        return;
+    }
+
+    let text = scoreboard_text(&scores, hud.scoreboard_order, &physics);
+    if scoreboard.0 != text {
+        scoreboard.0 = text;
+    }
+}
+
+/// Scores in the chosen order. Whoever would get the comeback bonus has a `*` after their score.
+fn scoreboard_text(
+    scores: &HashMap<usize, u32>,
+    order: ScoreboardOrder,
+    physics: &PhysicsConfig,
+) -> String {
+    let comeback = (physics.comeback_bonus > 0)
+        .then(|| comeback_candidate(scores))
+        .flatten();
+    let mark = |handle: usize| if Some(handle) == comeback { "*" } else { "" };
+
+    match order {
+        ScoreboardOrder::Handle => (0..scores.len())
+            .map(|handle| {
+                let score = scores[&handle];
+                Cow::<'static, str>::from(format!("{score}{}", mark(handle)))
+            })
+            .intersperse(" - ".into())
+            .collect(),
+        ScoreboardOrder::Score => ranked_scores(scores)
+            .into_iter()
+            .map(|(handle, score)| {
+                Cow::<'static, str>::from(format!("P{}: {score}{}", handle + 1, mark(handle)))
+            })
+            .intersperse(" - ".into())
+            .collect(),
+    }
+}
+
+/// The player in last place, who gets [`PhysicsConfig::comeback_bonus`] for a good round. Ties
+/// for last go to the highest handle, the same way [`ranked_scores`] orders them. Nobody if
+/// everyone is level.
+fn comeback_candidate(scores: &HashMap<usize, u32>) -> Option<usize> {
+    let ranked = ranked_scores(scores);
+    let (first, last) = (ranked.first()?, ranked.last()?);
+    (last.1 < first.1).then_some(last.0)
+}
+
+/// `(handle, score)` from highest score to lowest, ties in handle order so everyone sees the
+/// same ranking
+fn ranked_scores(scores: &HashMap<usize, u32>) -> Vec<(usize, u32)> {
+    let mut ranked: Vec<(usize, u32)> = scores.iter().map(|(h, s)| (*h, *s)).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+#[allow(clippy::too_many_arguments)]
+fn round_end_timeout(
+    mut timer: ResMut<RoundEndTimer>,
+    mut state: ResMut<NextState<RollbackState>>,
+    mut votes: ResMut<ContinueVotes>,
+    mut modifier_votes: ResMut<ModifierVotes>,
+    mut modifier: ResMut<RoundModifier>,
+    inputs: Res<PlayerInputs<GameConfig>>,
+    clock: Res<MatchClock>,
+    physics: Res<PhysicsConfig>,
+    scores: Res<Scores>,
+    time: Res<Time>,
+) {
+    let next_round = match physics.round_transition {
+        RoundTransition::Instant => true,
+        RoundTransition::Delayed => timer.tick(time.delta()).just_finished(),
+        RoundTransition::Manual => {
+            for (handle, (input, status)) in inputs.iter().enumerate() {
+                // Nobody's left to press anything for a disconnected player
+                let pressed = input.is_continue() || *status == InputStatus::Disconnected;
+                if pressed && !votes.contains(&handle) {
+                    votes.push(handle);
+                }
+            }
+            votes.len() == inputs.len()
+        }
+    };
+
+    if !next_round {
+        return;
+    }
+    votes.clear();
+
+    if physics.modifier_voting() {
+        *modifier = modifier_votes.winner();
+    }
+    *modifier_votes = ModifierVotes::default();
+
+    let time_up = clock.remaining_secs(&physics) == Some(0);
+    let target_reached =
+        physics.victory_score > 0 && scores.values().any(|score| *score >= physics.victory_score);
+    if (time_up || target_reached) && match_leader(&scores).is_some() {
+        state.set(RollbackState::MatchEnd);
+    } else {
+        // Still time left, or sudden death while the lead is tied
+        state.set(RollbackState::InRound);
+    }
+}
+
+/// Records each player's vote for the next round's [`RoundModifier`], from the keys they press.
+/// Dead players vote too, everyone's input is synced whether they're alive or not.
+fn cast_modifier_votes(
+    mut votes: ResMut<ModifierVotes>,
+    inputs: Res<PlayerInputs<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+) {
+    if !physics.modifier_voting() {
+        return;
+    }
+
+    // Whatever's held going into the round end was for steering, not voting
+    let first_frame = votes.held.is_empty();
+    votes.held.resize(inputs.len(), None);
+
+    for (handle, (input, status)) in inputs.iter().enumerate() {
+        if *status == InputStatus::Disconnected {
+            continue;
+        }
+        let pressed = RoundModifier::voted_by(*input);
+        let was_held = std::mem::replace(&mut votes.held[handle], pressed);
+        if first_frame || pressed == was_held {
+            continue;
+        }
+        let Some(vote) = pressed else {
+            continue;
+        };
+
+        match votes.iter_mut().find(|(voter, _)| *voter == handle) {
+            Some((_, old_vote)) => *old_vote = vote,
+            None => votes.push((handle, vote)),
+        }
+    }
+}
+
+/// Counts this frame's kills and who's still alive towards [`MatchStats`]
+fn tally_match_stats(
+    mut stats: ResMut<MatchStats>,
+    frame: Res<RollbackFrameCount>,
+    death_log: Res<DeathLog>,
+    players: Query<&Player>,
+) {
+    let frame = i32::from(*frame);
+    for death in death_log
+        .iter()
+        .rev()
+        .take_while(|death| death.frame == frame)
+    {
+        // Crashing into your own trail or forfeiting isn't a kill
+        if death.killer != death.handle
+            && let Some(kills) = stats.kills.get_mut(death.killer)
+        {
+            *kills += 1;
+        }
+    }
+
+    for player in &players {
+        if let Some(frames) = stats.frames_alive.get_mut(player.handle) {
+            *frames += 1;
+        }
+    }
+}
+
+fn tick_match_clock(mut clock: ResMut<MatchClock>) {
+    clock.frames += 1;
+}
+
+/// Pauses the round when someone with pauses left presses pause, and resumes it when they press
+/// it again or the pause runs out
+fn update_net_pause(
+    mut pause: ResMut<NetPause>,
+    physics: Res<PhysicsConfig>,
+    inputs: Res<PlayerInputs<GameConfig>>,
+) {
+    if physics.pauses_per_player == 0 {
+        return;
+    }
+
+    let num_players = inputs.len();
+    pause.used.resize(num_players, 0);
+    pause.held.resize(num_players, false);
+
+    // Only presses count, not holds
+    let mut pressed = Vec::new();
+    for (handle, (input, _)) in inputs.iter().enumerate() {
+        if input.is_pause() && !pause.held[handle] {
+            pressed.push(handle);
+        }
+        pause.held[handle] = input.is_pause();
+    }
+
+    match pause.by {
+        Some(by) => {
+            pause.frames_left = pause.frames_left.saturating_sub(1);
+            if pause.frames_left == 0 || pressed.contains(&by) {
+                pause.by = None;
+            }
+        }
+        None => {
+            // If more than one player presses it on the same frame, the lowest handle gets it
+            let Some(handle) = pressed
+                .into_iter()
+                .find(|handle| pause.used[*handle] < physics.pauses_per_player)
+            else {
+                return;
+            };
+            pause.used[handle] += 1;
+            pause.by = Some(handle);
+            pause.frames_left = physics.pause_frames;
+        }
+    }
+
+    if pause.by.is_some() {
+        pause.paused_frames += 1;
+    }
+}
+
+/// Run condition for everything that stops while the round is paused
+fn round_running(pause: Res<NetPause>) -> bool {
+    pause.by.is_none()
+}
+
+/// Player with the highest score, if nobody is tied with them
+fn match_leader(scores: &Scores) -> Option<usize> {
+    let best = scores.values().max()?;
+    let mut leaders = scores.iter().filter(|(_, score)| *score == best);
+    let (leader, _) = leaders.next()?;
+    leaders.next().is_none().then_some(*leader)
+}
+
+fn update_match_clock_text(
+    clock: Res<MatchClock>,
+    physics: Res<PhysicsConfig>,
+    mut text: Single<&mut TextSpan, With<MatchClockText>>,
+) {
+    let new_text = match clock.remaining_secs(&physics) {
+        None => String::new(),
+        Some(0) => "  Sudden death!".to_string(),
+        Some(secs) => format!("  {}:{:02}", secs / 60, secs % 60),
+    };
+
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+fn update_victory_text(
+    scores: Res<Scores>,
+    physics: Res<PhysicsConfig>,
+    mut text: Single<&mut TextSpan, With<VictoryText>>,
+) {
+    let new_text = if physics.victory_score == 0 {
+        String::new()
+    } else {
+        // Winning a round is worth a point for everyone else in it
+        let round_win = scores.len().saturating_sub(1) as u32;
+        let leader = match match_leader(&scores) {
+            Some(leader) if scores[&leader] + round_win >= physics.victory_score => {
+                format!("  Match point: Player {}", leader + 1)
+            }
+            Some(leader) => format!("  Player {} leads", leader + 1),
+            None => String::new(),
+        };
+        format!("  First to {}{leader}", physics.victory_score)
+    };
+
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+/// Shifts the arena towards red and pulses it as the match time limit gets close, more so in
+/// sudden death. Only the material changes, never anything simulated.
+fn show_overtime(
+    clock: Res<MatchClock>,
+    physics: Res<PhysicsConfig>,
+    accessibility: Res<AccessibilitySettings>,
+    time: Res<Time<Real>>,
+    arena: Single<&MeshMaterial3d<StandardMaterial>, With<Arena>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut was_active: Local<bool>,
+) {
+    let intensity = match clock.remaining_secs(&physics) {
+        None => 0.0,
+        Some(0) => 1.0,
+        Some(secs) => {
+            0.5 * OVERTIME_RAMP_SECS.saturating_sub(secs) as f32 / OVERTIME_RAMP_SECS as f32
+        }
+    };
+    if intensity == 0.0 && !*was_active {
+        return;
+    }
+    *was_active = intensity > 0.0;
+
+    let Some(material) = materials.get_mut(&arena.0) else {
+        return;
+    };
+
+    let calm = Color::srgba_u8(64, 198, 255, 104);
+    let heated = Color::srgba_u8(255, 64, 64, 104);
+    let pulse = if accessibility.reduced_motion {
+        // Just the color, no flashing
+        0.0
+    } else {
+        // Faster the closer to the end
+        let speed = 2.0 + 6.0 * intensity;
+        0.5 + 0.5 * (time.elapsed_secs() * speed).sin()
+    };
+
+    material.base_color = calm.mix(&heated, intensity);
+    material.emissive = heated.to_linear() * intensity * pulse * 0.5;
+}
+
+/// Applies [`RoundEndTrails`] to the finished round's trails
+fn show_round_end_trails(
+    settings: Res<GraphicsSettings>,
+    timer: Res<RoundEndTimer>,
+    trails: Query<&mut Visibility, With<TrailSegment>>,
+    trail_materials: Res<TrailMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    match settings.round_end_trails {
+        RoundEndTrails::Keep => {}
+        RoundEndTrails::Fade => {
+            // Fade most of the way out, but keep them readable until the very end
+            let fade = 1.0 - 0.8 * timer.fraction();
+            for bands in trail_materials.iter().flatten() {
+                for (band, handle) in bands.iter().enumerate() {
+                    if let Some(material) = materials.get_mut(handle) {
+                        material.base_color.set_alpha(trail_band_alpha(band) * fade);
+                        material.alpha_mode = AlphaMode::Blend;
+                    }
+                }
+            }
+        }
+        RoundEndTrails::Hide => {
+            for mut visibility in trails {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+fn toggle_standings(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<HudSettings>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        settings.show_standings = !settings.show_standings;
+    }
+}
+
+/// One line of the standings
+#[derive(Clone, PartialEq, Debug)]
+struct StandingsRow {
+    place: usize,
+    handle: usize,
+    score: u32,
+    /// Which elimination this round it was, `None` while alive
+    eliminated: Option<usize>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_standings(
+    mut commands: Commands,
+    settings: Res<HudSettings>,
+    session: Res<Session<GameConfig>>,
+    scores: Res<Scores>,
+    death_stack: Res<DeathStack>,
+    players: Query<&Player>,
+    standings: Query<Entity, With<Standings>>,
+    mut last_rows: Local<Option<Vec<StandingsRow>>>,
+) {
+    if !settings.show_standings {
+        for entity in &standings {
+            commands.entity(entity).despawn();
+        }
+        *last_rows = None;
+        return;
+    }
+
+    let by_score = ranked_scores(&scores);
+
+    let eliminations: Vec<usize> = death_stack.iter().flatten().copied().collect();
+    let rows: Vec<StandingsRow> = by_score
+        .iter()
+        .map(|(handle, score)| StandingsRow {
+            // Ties share a place
+            place: 1 + by_score.iter().filter(|(_, other)| other > score).count(),
+            handle: *handle,
+            score: *score,
+            eliminated: (!players.iter().any(|p| p.handle == *handle)).then(|| {
+                eliminations
+                    .iter()
+                    .position(|h| h == handle)
+                    .map_or(0, |i| i + 1)
+            }),
+        })
+        .collect();
+
+    if last_rows.as_ref() == Some(&rows) && !standings.is_empty() {
+        return;
+    }
+
+    for entity in &standings {
+        commands.entity(entity).despawn();
+    }
+
+    let num_players = session_num_players(&session);
+    commands
+        .spawn((
+            Standings,
+            DespawnOnExit(GameState::Playing),
+            Node {
+                position_type: PositionType::Absolute,
+                right: px(8),
+                top: px(64),
+                padding: UiRect::all(px(8)),
+                row_gap: px(4),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        ))
+        .with_children(|parent| {
+            for row in &rows {
+                let status = match row.eliminated {
+                    None => "alive".to_string(),
+                    Some(0) => "out".to_string(),
+                    Some(n) => format!("out #{n}"),
+                };
+                let alive_color = if row.eliminated.is_none() {
+                    slot_color(row.handle, num_players)
+                } else {
+                    Color::srgb(0.3, 0.3, 0.3)
+                };
+
+                parent.spawn((
+                    Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: px(8),
+                        ..default()
+                    },
+                    children![
+                        (
+                            Node {
+                                width: px(14),
+                                height: px(14),
+                                ..default()
+                            },
+                            BackgroundColor(alive_color),
+                        ),
+                        (
+                            Text::new(format!(
+                                "{}. Player {}  {}  ({status})",
+                                row.place,
+                                row.handle + 1,
+                                row.score
+                            )),
+                            TextFont {
+                                font_size: 20.,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ),
+                    ],
+                ));
+            }
+        });
+
+    *last_rows = Some(rows);
+}
+
+fn show_net_pause(
+    mut commands: Commands,
+    pause: Res<NetPause>,
+    physics: Res<PhysicsConfig>,
+    mut overlay: Query<(Entity, &mut Text), With<NetPauseText>>,
+) {
+    let Some(by) = pause.by else {
+        if let Ok((entity, _)) = overlay.single() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    let new_text = format!(
+        "PAUSED by Player {}\nResuming in {}s, or when they press P\n{} of {} pauses used",
+        by + 1,
+        pause.frames_left.div_ceil(FPS as u32),
+        pause.used.get(by).copied().unwrap_or_default(),
+        physics.pauses_per_player,
+    );
+
+    match overlay.single_mut() {
+        Ok((_, mut text)) => {
+            if text.0 != new_text {
+                text.0 = new_text;
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                NetPauseText,
+                DespawnOnExit(GameState::Playing),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(35.0),
+                    justify_self: JustifySelf::Center,
+                    padding: UiRect::all(px(16)),
+                    ..default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.6)),
+                Text::new(new_text),
+                TextFont {
+                    font_size: 36.,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        }
+    }
+}
+
+fn show_last_stand(
+    mut commands: Commands,
+    session: Res<Session<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+    round_state: Res<State<RollbackState>>,
+    players: Query<(), With<Player>>,
+    text: Query<Entity, With<LastStandText>>,
+) {
+    let active = *round_state.get() == RollbackState::InRound
+        && is_last_stand(
+            players.iter().len(),
+            session_num_players(&session),
+            &physics,
+        );
+
+    match (active, text.single()) {
+        (true, Err(_)) => {
+            commands.spawn((
+                LastStandText,
+                DespawnOnExit(GameState::Playing),
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: px(16),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                children![(
+                    Text::new("Last stand!"),
+                    TextFont {
+                        font_size: 48.,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(1.0, 0.3, 0.1)),
+                )],
+            ));
+        }
+        (false, Ok(entity)) => {
+            commands.entity(entity).despawn();
+        }
+        _ => {}
+    }
+}
+
+/// Counts down the whole seconds left of the [`RoundCountdown`], in the middle of the screen
+fn show_round_countdown(
+    mut commands: Commands,
+    round_state: Res<State<RollbackState>>,
+    countdown: Res<RoundCountdown>,
+    text: Query<(Entity, &RoundCountdownText)>,
+) {
+    let secs = if *round_state.get() == RollbackState::InRound {
+        countdown.div_ceil(FPS as u32)
+    } else {
+        0
+    };
+
+    let shown = text.single().ok();
+    if shown.is_some_and(|(_, shown)| shown.0 == secs) {
+        return;
+    }
+    if let Some((entity, _)) = shown {
+        commands.entity(entity).despawn();
+    }
+    if secs == 0 {
+        return;
+    }
+
+    commands.spawn((
+        RoundCountdownText(secs),
+        DespawnOnExit(GameState::Playing),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![(
+            Text::new(secs.to_string()),
+            TextFont {
+                font_size: 96.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )],
+    ));
+}
+
+/// Explains the freeze when we've predicted as far ahead as
+/// [`max_prediction_frames`](crate::settings::NetworkSettings::max_prediction_frames) allows and
+/// GGRS won't advance until a peer's inputs turn up
+fn show_slow_peer(
+    mut commands: Commands,
+    session: Res<Session<GameConfig>>,
+    time: Res<Time<Real>>,
+    text: Query<Entity, With<SlowPeerText>>,
+    mut stalled_secs: Local<f32>,
+) {
+    let stalled = match session.as_ref() {
+        Session::P2P(s) => s.current_frame() - s.confirmed_frame() >= s.max_prediction() as i32,
+        _ => false,
+    };
+    *stalled_secs = if stalled {
+        *stalled_secs + time.delta_secs()
+    } else {
+        0.0
+    };
+
+    match (*stalled_secs >= SLOW_PEER_SECS, text.single()) {
+        (true, Err(_)) => {
+            commands.spawn((
+                SlowPeerText,
+                DespawnOnExit(GameState::Playing),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: px(64),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                children![(
+                    Text::new("Waiting for slow peer..."),
+                    TextFont {
+                        font_size: 33.,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(1.0, 0.85, 0.2)),
+                )],
+            ));
+        }
+        (false, Ok(entity)) => {
+            commands.entity(entity).despawn();
+        }
+        _ => {}
+    }
+}
+
+/// Counts how long Ctrl+Backspace has been held, showing a prompt until it's long enough to
+/// forfeit so it can't happen by accident
+fn update_forfeit_hold(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    round_state: Res<State<RollbackState>>,
+    time: Res<Time<Real>>,
+    mut hold: ResMut<ForfeitHold>,
+    mut prompt: Query<(Entity, &mut Text), With<ForfeitPrompt>>,
+) {
+    let held = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && keyboard_input.pressed(KeyCode::Backspace)
+        && *round_state.get() == RollbackState::InRound;
+    hold.secs = if held {
+        hold.secs + time.delta_secs()
+    } else {
+        0.0
+    };
+
+    let remaining = FORFEIT_HOLD_SECS - hold.secs;
+    match (held && remaining > 0.0, prompt.single_mut()) {
+        (true, Ok((_, mut text))) => {
+            text.0 = format!("Keep holding to forfeit... {remaining:.1}");
+        }
+        (true, Err(_)) => {
+            commands.spawn((
+                ForfeitPrompt,
+                DespawnOnExit(GameState::Playing),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: px(80),
+                    justify_self: JustifySelf::Center,
+                    ..default()
+                },
+                Text::new(format!("Keep holding to forfeit... {remaining:.1}")),
+                TextFont {
+                    font_size: 32.,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        }
+        (false, Ok((entity, _))) => {
+            commands.entity(entity).despawn();
+        }
+        (false, Err(_)) => {}
+    }
+}
+
+/// Moves or hides the scoreboard as the HUD settings say
+fn apply_hud_settings(
+    settings: Res<HudSettings>,
+    mut scoreboard: Query<(&mut Node, &mut Visibility, &ChildOf), With<Scoreboard>>,
+    added: Query<(), Added<Scoreboard>>,
+    mut containers: Query<&mut Node, Without<Scoreboard>>,
+) {
+    if !settings.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let (vertical, horizontal) = settings.scoreboard_position.alignment();
+    for (mut node, mut visibility, child_of) in &mut scoreboard {
+        node.align_self = horizontal;
+        visibility.set_if_neq(if settings.show_scoreboard {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+
+        if let Ok(mut container) = containers.get_mut(child_of.parent()) {
+            container.justify_content = vertical;
+        }
+    }
+}
+
+/// Announces the round's winner, or a double KO, until the next round starts, and the match's
+/// winner once it's over
+#[allow(clippy::too_many_arguments)]
+fn update_round_banner(
+    mut commands: Commands,
+    session: Res<Session<GameConfig>>,
+    round_state: Res<State<RollbackState>>,
+    death_stack: Res<DeathStack>,
+    scores: Res<Scores>,
+    stats: Res<MatchStats>,
+    physics: Res<PhysicsConfig>,
+    players: Query<&Player>,
+    banner: Query<Entity, With<RoundBanner>>,
+) {
+    if !round_state.is_changed() {
+        return;
+    }
+
+    for entity in &banner {
+        commands.entity(entity).despawn();
+    }
+
+    let text = if *round_state.get() == RollbackState::MatchEnd {
+        let winner = match match_leader(&scores) {
+            Some(winner) => format!("Player {} wins the match!", winner + 1),
+            None => "Match over!".to_string(),
+        };
+        match stats.mvp() {
+            Some(mvp) => format!(
+                "{winner}\nMVP: Player {} ({} kills, {}s alive)",
+                mvp + 1,
+                stats.kills[mvp],
+                stats.frames_alive[mvp] / FPS as u32
+            ),
+            None => winner,
+        }
+    } else if *round_state.get() != RollbackState::RoundEnd {
+        return;
+    } else if physics.casual.is_some() {
+        "Time's up!".to_string()
+    } else if session_num_players(&session) == 1 {
+        "Crashed!".to_string()
+    } else if death_stack.is_double_ko(players.iter().len()) {
+        "Double KO!".to_string()
+    } else if let Ok(winner) = players.single() {
+        format!("Player {} wins the round!", winner.handle + 1)
+    } else {
+        "Draw!".to_string()
+    };
+    let text = if *round_state.get() == RollbackState::RoundEnd
+        && physics.round_transition == RoundTransition::Manual
+    {
+        format!("{text}\nPress Enter to continue")
+    } else {
+        text
+    };
+
+    let banner = commands
+        .spawn((
+            RoundBanner,
+            DespawnOnExit(GameState::Playing),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            children![(
+                Text::new(text),
+                TextFont {
+                    font_size: 64.,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            )],
+        ))
+        .id();
+
+    if *round_state.get() == RollbackState::RoundEnd && physics.modifier_voting() {
+        commands.entity(banner).with_child((
+            ModifierVoteText,
+            Text::default(),
+            TextFont {
+                font_size: 28.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+    }
+}
+
+/// Shows how the vote for the next round's [`RoundModifier`] is going. Votes are predicted like
+/// everything else, so a late input can still change what's shown.
+fn update_modifier_vote_text(
+    votes: Res<ModifierVotes>,
+    mut text: Single<&mut Text, With<ModifierVoteText>>,
+) {
+    let options: Vec<String> = RoundModifier::ALL
+        .iter()
+        .map(|modifier| {
+            format!(
+                "{}: {} ({})",
+                modifier.key_name(),
+                modifier.name(),
+                votes.count(*modifier)
+            )
+        })
+        .collect();
+    let new_text = format!("Vote for the next round\n{}", options.join("   "));
+
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+/// Undoes any fading from the last round, since the materials are shared with the new trails
+fn restore_trail_materials(
+    trail_materials: Res<TrailMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for bands in trail_materials.iter().flatten() {
+        for (band, handle) in bands.iter().enumerate() {
+            if let Some(material) = materials.get_mut(handle) {
+                material.base_color.set_alpha(trail_band_alpha(band));
+                material.alpha_mode = trail_band_alpha_mode(band);
+            }
+        }
+    }
+}
+
+/// Pulses the glow of each player's trail materials, each player at a different point in the
+/// cycle. Only touches the shared materials, so it costs the same however long trails get.
+fn pulse_trail_owners(
+    settings: Res<GraphicsSettings>,
+    accessibility: Res<AccessibilitySettings>,
+    time: Res<Time<Real>>,
+    trail_materials: Res<TrailMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut was_on: Local<bool>,
+) {
+    let on = settings.trail_owner_pulse && !accessibility.reduced_motion;
+    if !on && !*was_on {
+        return;
+    }
+    *was_on = on;
+
+    let num_players = trail_materials.len();
+    for (handle, kinds) in trail_materials.iter().enumerate() {
+        let phase = handle as f32 / num_players as f32 * 2.0 * PI;
+        let glow = if on {
+            0.15 + 0.15 * (time.elapsed_secs() * 3.0 + phase).sin()
+        } else {
+            0.0
+        };
+        let emissive = slot_color(handle, num_players).to_linear() * glow;
+
+        for material in kinds.iter().flatten() {
+            if let Some(material) = materials.get_mut(material) {
+                material.emissive = emissive;
+            }
+        }
+    }
+}
+
+/// Moves older trail segments onto more see-through materials. Only how they look, they're just
+/// as deadly. Uses a handful of shared materials rather than one per segment.
+fn fade_trails_by_age(
+    settings: Res<GraphicsSettings>,
+    trail_materials: Res<TrailMaterials>,
+    mut trails: Query<(&TrailSegment, &mut MeshMaterial3d<StandardMaterial>)>,
+) {
+    // Segments are timed on the rollback clock, so measure age against the newest one
+    let newest = trails
+        .iter()
+        .map(|(segment, _)| segment.created_at)
+        .fold(f64::MIN, f64::max);
+
+    for (segment, mut material) in &mut trails {
+        let band = if settings.trail_fade_secs > 0.0 {
+            let age = ((newest - segment.created_at) as f32 / settings.trail_fade_secs).min(1.0);
+            (age * (TRAIL_AGE_BANDS - 1) as f32) as usize
+        } else {
+            0
+        };
+
+        let Some(band_material) = trail_materials.get(segment.owner, segment.dashed, band) else {
+            continue;
+        };
+        if material.0 != *band_material {
+            material.0 = band_material.clone();
+        }
+    }
+}
+
+fn spawn_local_player_marker(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        LocalPlayerMarker,
+        DespawnOnExit(GameState::Playing),
+        Mesh3d(meshes.add(Cone::new(0.12, 0.25))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..default()
+        })),
+        Visibility::Hidden,
+    ));
+
+    commands.spawn((
+        LocalPlayerLabel,
+        DespawnOnExit(GameState::Playing),
+        Node {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        Text::new("YOU"),
+        TextFont {
+            font_size: 20.,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Visibility::Hidden,
+    ));
+}
+
+/// Keeps the marker and label over the local player, hiding them once they're dead
+#[allow(clippy::type_complexity)]
+fn update_local_player_marker(
+    local_players: Res<LocalPlayers>,
+    players: Query<(&Transform, &Player), Without<LocalPlayerMarker>>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    ui_scale: Res<UiScale>,
+    mut marker: Single<(&mut Transform, &mut Visibility), With<LocalPlayerMarker>>,
+    mut label: Single<
+        (&mut Node, &mut Visibility),
+        (With<LocalPlayerLabel>, Without<LocalPlayerMarker>),
+    >,
+) {
+    let local = players
+        .iter()
+        .find(|(_, p)| local_players.0.contains(&p.handle))
+        .map(|(transform, _)| transform);
+
+    let Some(player_transform) = local else {
+        marker.1.set_if_neq(Visibility::Hidden);
+        label.1.set_if_neq(Visibility::Hidden);
+        return;
+    };
+
+    let up = player_transform.translation.normalize_or_zero();
+    let marker_pos = player_transform.translation + up * 0.8;
+    // Cones point along their local Y, so flip it to point down at the player
+    *marker.0 = Transform::from_translation(marker_pos)
+        .with_rotation(Quat::from_rotation_arc(Vec3::Y, -up));
+    marker.1.set_if_neq(Visibility::Inherited);
+
+    let (camera, camera_transform) = *camera;
+    match camera.world_to_viewport(camera_transform, marker_pos + up * 0.3) {
+        Ok(viewport_pos) => {
+            // Node positions get scaled by UiScale, viewport positions don't
+            let pos = viewport_pos / ui_scale.0;
+            label.0.left = px(pos.x - 16.0);
+            label.0.top = px(pos.y - 24.0);
+            label.1.set_if_neq(Visibility::Inherited);
+        }
+        Err(_) => {
+            label.1.set_if_neq(Visibility::Hidden);
+        }
+    }
+}
+
+/// Moves each player's model to their simulated position. Remote players glide there according
+/// to the smoothing setting, hiding the jumps rollbacks make when they correct a misprediction.
+fn sync_player_visuals(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GraphicsSettings>,
+    local_players: Res<LocalPlayers>,
+    time: Res<Time<Real>>,
+    players: Query<(&Transform, &Player), Without<PlayerVisual>>,
+    mut visuals: Query<(Entity, &PlayerVisual, &mut Transform)>,
+) {
+    // Fraction of the way to move this frame, the same at any frame rate
+    let smoothing = settings.remote_smoothing.clamp(0.0, 0.95);
+    let catch_up = 1.0 - smoothing.powf(time.delta_secs() * 60.0);
+
+    let mut has_visual = Vec::new();
+    for (entity, visual, mut transform) in &mut visuals {
+        let Some((target, player)) = players.iter().find(|(_, p)| p.handle == visual.handle) else {
+            // Dead
+            commands.entity(entity).despawn();
+            continue;
+        };
+        has_visual.push(visual.handle);
+
+        // Our own inputs are never mispredicted, and big jumps are respawns, not corrections
+        if local_players.0.contains(&player.handle)
+            || transform.translation.distance(target.translation) > 2.0
+        {
+            *transform = *target;
+        } else {
+            transform.translation = transform.translation.lerp(target.translation, catch_up);
+            transform.rotation = transform.rotation.slerp(target.rotation, catch_up);
+        }
+    }
+
+    for (transform, player) in &players {
+        if has_visual.contains(&player.handle) {
+            continue;
+        }
+
+        commands.spawn((
+            PlayerVisual {
+                handle: player.handle,
+            },
+            DespawnOnExit(GameState::Playing),
+            *transform,
+            SceneRoot(
+                asset_server
+                    .load(GltfAssetLabel::Scene(0).from_asset("models/AlienCake/alien.glb")),
+            ),
+        ));
+    }
+}
+
+/// Shakes the camera when the local player dies or kills someone, once the death is confirmed so
+/// a mispredicted death never shakes and a resimulated one doesn't shake twice
+fn start_screen_shake(
+    mut deaths: NewlyConfirmed<DeathLog>,
+    local_players: Res<LocalPlayers>,
+    settings: Res<GraphicsSettings>,
+    accessibility: Res<AccessibilitySettings>,
+    mut shake: ResMut<ScreenShake>,
+    time: Res<Time<Real>>,
+) {
+    shake.timer.tick(time.delta());
+
+    for death in deaths.read() {
+        let died = local_players.0.contains(&death.handle);
+        let killed = local_players.0.contains(&death.killer) && death.killer != death.handle;
+        if (died || killed) && !accessibility.reduced_motion && settings.screen_shake > 0.0 {
+            // Dying hits harder than getting a kill
+            shake.intensity = settings.screen_shake * if died { 1.0 } else { 0.5 };
+            shake.timer = Timer::from_seconds(settings.screen_shake_secs, TimerMode::Once);
+        }
+    }
+}
+
+/// Rumbles every connected gamepad for the local player's deaths and dashes. There's only ever
+/// one local player, so there's no need to work out whose gamepad is whose.
+///
+/// Deaths wait until they're confirmed, like [`start_screen_shake`]. Dashes only depend on our
+/// own inputs, which are never mispredicted, so they're read straight off the local player. Both
+/// run once per rendered frame however many frames GGRS resimulated, so nothing rumbles twice.
+fn rumble_gamepads(
+    mut deaths: NewlyConfirmed<DeathLog>,
+    local_players: Res<LocalPlayers>,
+    controls: Res<ControlSettings>,
+    players: Query<&Player>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    mut rumble: MessageWriter<GamepadRumbleRequest>,
+    mut last_dash: Local<Option<(u32, u32)>>,
+) {
+    let mut request = |intensity: GamepadRumbleIntensity, secs: f32| {
+        if !controls.haptics {
+            return;
+        }
+        for gamepad in &gamepads {
+            rumble.write(GamepadRumbleRequest::Add {
+                gamepad,
+                intensity,
+                duration: std::time::Duration::from_secs_f32(secs),
+            });
+        }
+    };
+
+    for death in deaths.read() {
+        if local_players.0.contains(&death.handle) {
+            request(GamepadRumbleIntensity::MAX, 0.4);
+        }
+    }
+
+    let dash = players
+        .iter()
+        .find(|p| local_players.0.contains(&p.handle))
+        .map(|p| (p.dashing, p.dash_cooldown));
+    if let (Some((was_dashing, was_cooling)), Some((dashing, cooling))) = (*last_dash, dash) {
+        if was_dashing == 0 && dashing > 0 {
+            request(GamepadRumbleIntensity::weak_motor(0.6), 0.15);
+        } else if was_cooling > 0 && cooling == 0 && dashing == 0 {
+            // Dash is ready again
+            request(GamepadRumbleIntensity::weak_motor(0.3), 0.05);
+        }
+    }
+    *last_dash = dash;
+}
+
+/// Flashes the screen once someone's eraser pickup is confirmed
+fn flash_trail_eraser(
+    mut commands: Commands,
+    mut erasers: NewlyConfirmed<EraserLog>,
+    accessibility: Res<AccessibilitySettings>,
+    time: Res<Time<Real>>,
+    mut flashes: Query<(Entity, &mut EraserFlash, &mut BackgroundColor)>,
+) {
+    for (entity, mut flash, mut background) in &mut flashes {
+        flash.timer.tick(time.delta());
+        if flash.timer.is_finished() {
+            commands.entity(entity).despawn();
+        } else {
+            background
+                .0
+                .set_alpha(flash.alpha * (1.0 - flash.timer.fraction()));
+        }
+    }
+
+    for _ in erasers.read() {
+        // A softer flash for anyone sensitive to them
+        let alpha = if accessibility.reduced_motion {
+            0.15
+        } else {
+            0.5
+        };
+        commands.spawn((
+            EraserFlash {
+                timer: Timer::from_seconds(ERASER_FLASH_SECS, TimerMode::Once),
+                alpha,
+            },
+            DespawnOnExit(GameState::Playing),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            BackgroundColor(Color::WHITE.with_alpha(alpha)),
+        ));
+    }
+}
+
+/// Leaves a burst in the victim's color where each player dies, once the death is confirmed so
+/// it only happens once per death
+fn spawn_death_bursts(
+    mut commands: Commands,
+    mut deaths: NewlyConfirmed<DeathLog>,
+    session: Res<Session<GameConfig>>,
+    settings: Res<GraphicsSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let num_players = session_num_players(&session);
+    for death in deaths.read() {
+        let expand = match settings.death_effects {
+            DeathEffects::Full => false,
+            DeathEffects::Simple => true,
+            DeathEffects::Off => continue,
+        };
+
+        let color = slot_color(death.handle, num_players);
+        let material = materials.add(StandardMaterial {
+            base_color: color,
+            emissive: color.to_linear() * 2.0,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+
+        let mut burst = commands.spawn((
+            DeathBurst {
+                timer: Timer::from_seconds(DEATH_BURST_SECS, TimerMode::Once),
+                material: material.clone(),
+                expand,
+            },
+            DespawnOnExit(GameState::Playing),
+            Transform::from_translation(death.position),
+            Visibility::default(),
+        ));
+
+        if expand {
+            burst.with_child((
+                Mesh3d(meshes.add(Sphere::new(PLAYER_RADIUS))),
+                MeshMaterial3d(material),
+            ));
+            continue;
+        }
+
+        // Spread out around the surface, with a little lift off it
+        let up = death.position.normalize_or(Vec3::Y);
+        let (a, b) = up.any_orthonormal_pair();
+        let spark = meshes.add(Sphere::new(0.04));
+        burst.with_children(|parent| {
+            for i in 0..DEATH_SPARKS {
+                let angle = i as f32 / DEATH_SPARKS as f32 * 2.0 * PI;
+                let direction = a * angle.cos() + b * angle.sin() + up * 0.5;
+                parent.spawn((
+                    DeathSpark {
+                        velocity: direction.normalize() * 2.5,
+                    },
+                    Mesh3d(spark.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::default(),
+                ));
+            }
+        });
+    }
+}
+
+/// Moves sparks out, grows flashes and fades both, clearing each burst away once it's done
+fn animate_death_bursts(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut bursts: Query<(Entity, &mut DeathBurst, &mut Transform), Without<DeathSpark>>,
+    mut sparks: Query<(&DeathSpark, &mut Transform)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let dt = time.delta_secs();
+    for (spark, mut transform) in &mut sparks {
+        transform.translation += spark.velocity * dt;
+    }
+
+    for (entity, mut burst, mut transform) in &mut bursts {
+        burst.timer.tick(time.delta());
+        if burst.timer.is_finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let fraction = burst.timer.fraction();
+        if burst.expand {
+            transform.scale = Vec3::splat(1.0 + 3.0 * fraction);
+        }
+        if let Some(material) = materials.get_mut(&burst.material) {
+            material.base_color.set_alpha(1.0 - fraction);
+        }
+    }
+}
+
+/// Outlines the segment the local player ran into, once their death is confirmed
+fn highlight_killing_trail(
+    mut commands: Commands,
+    mut deaths: NewlyConfirmed<DeathLog>,
+    local_players: Res<LocalPlayers>,
+    time: Res<Time<Real>>,
+    trails: Query<(&Transform, &Mesh3d, &TrailSegment)>,
+    mut highlights: Query<(Entity, &mut KillHighlight)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, mut highlight) in &mut highlights {
+        highlight.timer.tick(time.delta());
+        if highlight.timer.is_finished() {
+            materials.remove(&highlight.material);
+            commands.entity(entity).despawn();
+        } else if let Some(material) = materials.get_mut(&highlight.material) {
+            material
+                .base_color
+                .set_alpha(0.6 * (1.0 - highlight.timer.fraction()));
+        }
+    }
+
+    for death in deaths.read() {
+        let Some(trail_frame) = death.trail_frame else {
+            continue;
+        };
+        if !local_players.0.contains(&death.handle) {
+            continue;
+        }
+        let Some((transform, mesh, _)) = trails.iter().find(|(_, _, segment)| {
+            segment.owner == death.killer && segment.created_frame == trail_frame
+        }) else {
+            // Already cleared away
+            continue;
+        };
+
+        let material = materials.add(StandardMaterial {
+            base_color: Color::WHITE.with_alpha(0.6),
+            emissive: LinearRgba::WHITE,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        commands.spawn((
+            KillHighlight {
+                timer: Timer::from_seconds(KILL_HIGHLIGHT_SECS, TimerMode::Once),
+                material: material.clone(),
+            },
+            DespawnOnExit(GameState::Playing),
+            mesh.clone(),
+            MeshMaterial3d(material),
+            // Fatter than the segment so it shows around it, not just through it
+            transform.with_scale(transform.scale * Vec3::new(1.6, 1.05, 1.6)),
+        ));
+    }
+}
+
+/// Pulses the pad and plays a whoosh for each boost, once it's confirmed so it only happens once
+fn cue_boost_pads(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<AudioSettings>,
+    mut boosts: NewlyConfirmed<BoostLog>,
+    time: Res<Time<Real>>,
+    mut pads: Query<(
+        Entity,
+        &BoostPad,
+        &mut Transform,
+        Option<&mut BoostPadPulse>,
+    )>,
+) {
+    for (entity, _, mut transform, pulse) in &mut pads {
+        let Some(mut pulse) = pulse else {
+            continue;
+        };
+
+        pulse.0.tick(time.delta());
+        if pulse.0.is_finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<BoostPadPulse>();
+        } else {
+            // Wide and flat, a pad shouldn't grow up off the surface
+            let grow = 1.0 + 0.5 * (1.0 - pulse.0.fraction());
+            transform.scale = Vec3::new(grow, 1.0, grow);
+        }
+    }
+
+    for boost in boosts.read() {
+        if let Some((entity, ..)) = pads.iter().find(|(_, pad, ..)| pad.index == boost.pad) {
+            commands
+                .entity(entity)
+                .insert(BoostPadPulse(Timer::from_seconds(
+                    BOOST_PAD_PULSE_SECS,
+                    TimerMode::Once,
+                )));
+        }
+        commands.spawn((
+            AudioPlayer::new(asset_server.load("sounds/boost.wav")),
+            PlaybackSettings::DESPAWN.with_volume(audio.volume(SoundKind::Sfx)),
+            SoundKind::Sfx,
+            DespawnOnExit(GameState::Playing),
+        ));
+    }
+}
+
+fn apply_screen_shake(
+    time: Res<Time<Real>>,
+    mut shake: ResMut<ScreenShake>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+) {
+    if shake.timer.is_finished() {
+        return;
+    }
+
+    // Cheap noise, it only has to look jittery
+    let t = time.elapsed_secs();
+    let direction = Vec3::new(
+        (t * 53.0).sin(),
+        (t * 71.0 + 1.0).sin(),
+        (t * 37.0 + 2.0).sin(),
+    );
+    let offset = direction * shake.intensity * (1.0 - shake.timer.fraction());
+
+    camera.translation += offset;
+    shake.applied = offset;
+}
+
+fn remove_screen_shake(
+    mut shake: ResMut<ScreenShake>,
+    camera: Option<Single<&mut Transform, With<Camera3d>>>,
+) {
+    if let Some(mut camera) = camera {
+        camera.translation -= shake.applied;
+    }
+    shake.applied = Vec3::ZERO;
+}
+
+/// Draws where the local player will be over the next second if they keep doing what they're
+/// doing, by running [`move_on_sphere`] ahead on a copy of them
+#[allow(clippy::too_many_arguments)]
+fn show_predicted_path(
+    settings: Res<GraphicsSettings>,
+    local_players: Res<LocalPlayers>,
+    session: Res<Session<GameConfig>>,
+    physics: Res<PhysicsConfig>,
+    modifier: Res<RoundModifier>,
+    handicaps: Res<Handicaps>,
+    players: Query<(&Transform, &Player)>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.predicted_path {
+        return;
+    }
+
+    let dt = 1.0 / FPS as f32;
+    let speed_multiplier = if is_last_stand(
+        players.iter().len(),
+        session_num_players(&session),
+        &physics,
+    ) {
+        physics.last_stand_speed
+    } else {
+        1.0
+    };
+    let num_players = session_num_players(&session);
+
+    for (transform, player) in &players {
+        if !local_players.0.contains(&player.handle) {
+            continue;
+        }
+
+        let mut ghost_transform = *transform;
+        let mut ghost = player.clone();
+        let mut points = vec![ghost_transform.translation];
+        let speed_multiplier = speed_multiplier * handicaps.get(player.handle).speed();
+        for _ in 0..FPS {
+            let move_amount = ground_speed(&ghost, &physics, *modifier, speed_multiplier) * dt;
+            move_on_sphere(&mut ghost_transform, ghost.turn_velocity * dt, move_amount);
+            ghost.dashing = ghost.dashing.saturating_sub(1);
+            ghost.boost = ghost.boost.saturating_sub(1);
+            points.push(ghost_transform.translation);
+        }
+
+        // Just above the surface so the sphere doesn't hide it
+        let color = slot_color(player.handle, num_players).with_alpha(0.6);
+        gizmos.linestrip(
+            points
+                .into_iter()
+                .map(|point| point.normalize_or_zero() * (SPHERE_RADIUS + 0.05)),
+            color,
+        );
+    }
+}
+
+/// Faint streaks showing which way the wind is blowing, spread evenly over the sphere
+fn show_wind(
+    physics: Res<PhysicsConfig>,
+    wind: Res<Wind>,
+    clock: Res<MatchClock>,
+    mut gizmos: Gizmos,
+) {
+    if physics.wind_strength == 0.0 {
+        return;
+    }
+
+    const STREAKS: usize = 160;
+    let golden_angle = PI * (3.0 - ops::sqrt(5.0));
+    for i in 0..STREAKS {
+        let y = 1.0 - 2.0 * (i as f32 + 0.5) / STREAKS as f32;
+        let ring_radius = ops::sqrt(1.0 - y * y);
+        let theta = golden_angle * i as f32;
+        let start = Vec3::new(
+            ops::cos(theta) * ring_radius,
+            y,
+            ops::sin(theta) * ring_radius,
+        ) * (SPHERE_RADIUS + 0.05);
+        let streak = wind_at(start, &wind, &clock, &physics) * 0.15;
+        gizmos.line(start, start + streak, Color::WHITE.with_alpha(0.25));
+    }
+}
+
+/// Starts the intro sweep when a round starts, and skips it on any key
+fn start_round_intro(
+    settings: Res<CameraSettings>,
+    accessibility: Res<AccessibilitySettings>,
+    physics: Res<PhysicsConfig>,
+    round_state: Res<State<RollbackState>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    mut intro: ResMut<RoundIntro>,
+) {
+    if round_state.is_changed() && *round_state.get() == RollbackState::InRound {
+        // A whole lap of the sphere is a lot of motion. It's over when the countdown is, so
+        // there's nothing to show without one.
+        if settings.round_intro
+            && settings.mode == CameraMode::Follow
+            && !accessibility.reduced_motion
+            && physics.round_countdown_frames > 0
+        {
+            intro.timer = Timer::from_seconds(
+                physics.round_countdown_frames as f32 / FPS as f32,
+                TimerMode::Once,
+            );
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        intro.timer.finish();
+    }
+    intro.timer.tick(time.delta());
+}
+
+fn apply_round_intro(
+    mut intro: ResMut<RoundIntro>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+) {
+    if intro.timer.is_finished() {
+        return;
+    }
+
+    let follow = **camera;
+    intro.follow = Some(follow);
+
+    // One lap around an axis square to where the follow camera is, so it passes over the whole
+    // sphere and ends up back above the player
+    let start = follow.translation.try_normalize().unwrap_or(Vec3::Z);
+    let axis = start.any_orthonormal_vector();
+    let t = intro.timer.fraction();
+    let direction = Quat::from_axis_angle(axis, t * 2.0 * PI) * start;
+    let sweep =
+        Transform::from_translation(direction * SPHERE_RADIUS * 3.0).looking_at(Vec3::ZERO, axis);
+
+    // Ease out of the follow camera at the start and back into it at the end, so there's no cut
+    let blend = (1.0 - t / 0.15).max((t - 0.7) / 0.3).clamp(0.0, 1.0);
+    let blend = blend * blend * (3.0 - 2.0 * blend);
+    camera.translation = sweep.translation.lerp(follow.translation, blend);
+    camera.rotation = sweep.rotation.slerp(follow.rotation, blend);
+}
+
+fn remove_round_intro(
+    mut intro: ResMut<RoundIntro>,
+    camera: Option<Single<&mut Transform, With<Camera3d>>>,
+) {
+    if let (Some(follow), Some(mut camera)) = (intro.follow.take(), camera) {
+        **camera = follow;
+    }
+}
+
+/// Points an arrow the way each player is facing when a round starts, fading out as they get going
+#[allow(clippy::too_many_arguments)]
+fn show_spawn_indicators(
+    mut commands: Commands,
+    settings: Res<GraphicsSettings>,
+    round_state: Res<State<RollbackState>>,
+    time: Res<Time<Real>>,
+    mut shown_at: Local<f32>,
+    players: Query<(&Transform, &Player), Without<SpawnIndicator>>,
+    mut indicators: Query<(
+        Entity,
+        &SpawnIndicator,
+        &mut Transform,
+        &mut Visibility,
+        &MeshMaterial3d<StandardMaterial>,
+    )>,
+    session: Res<Session<GameConfig>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let now = time.elapsed_secs();
+
+    if round_state.is_changed() && *round_state.get() == RollbackState::InRound {
+        for (entity, ..) in &indicators {
+            commands.entity(entity).despawn();
+        }
+
+        if !settings.spawn_indicators {
+            return;
+        }
+
+        *shown_at = now;
+        let num_players = session_num_players(&session);
+        let mesh = meshes.add(Cone::new(0.12, 0.3));
+        for handle in 0..num_players {
+            commands.spawn((
+                DespawnOnExit(GameState::Playing),
+                SpawnIndicator { handle },
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: slot_color(handle, num_players),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })),
+                // Hidden until it's been placed in front of its player
+                Visibility::Hidden,
+            ));
+        }
+        return;
+    }
+
+    let fraction = (now - *shown_at) / SPAWN_INDICATOR_SECS;
+    for (entity, indicator, mut transform, mut visibility, material) in &mut indicators {
+        if fraction >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let Some((player_transform, _)) =
+            players.iter().find(|(_, p)| p.handle == indicator.handle)
+        else {
+            // Not spawned yet, or already dead
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+
+        let forward = player_transform.forward().as_vec3();
+        let up = player_transform.translation.normalize_or_zero();
+        transform.translation = player_transform.translation + forward * 0.6 + up * 0.2;
+        // Cones point along their local Y
+        transform.rotation = Quat::from_rotation_arc(Vec3::Y, forward);
+        visibility.set_if_neq(Visibility::Inherited);
+
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.base_color.set_alpha(1.0 - fraction);
+        }
+    }
+}
+
+/// Once the local player dies, follow someone still alive instead. Left/right switch who.
+fn update_spectate_target(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    local_players: Res<LocalPlayers>,
+    players: Query<&Player>,
+    mut target: ResMut<SpectateTarget>,
+) {
+    let mut alive: Vec<usize> = players.iter().map(|p| p.handle).collect();
+
+    if alive.iter().any(|handle| local_players.0.contains(handle)) || alive.is_empty() {
+        // Back to following ourselves, or nobody left to watch
+        target.set_if_neq(SpectateTarget(None));
+        return;
+    }
+
+    alive.sort();
+    let current = target
+        .and_then(|handle| alive.iter().position(|h| *h == handle))
+        .unwrap_or(0);
+
+    let next = if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        (current + 1) % alive.len()
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        (current + alive.len() - 1) % alive.len()
+    } else {
+        current
+    };
+
+    target.set_if_neq(SpectateTarget(Some(alive[next])));
+}
+
+fn toggle_camera_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        settings.mode = match settings.mode {
+            CameraMode::Follow => CameraMode::Overview,
+            CameraMode::Overview => CameraMode::Follow,
+        };
+    }
+}
+
+/// Orbits the whole sphere in [`CameraMode::Overview`], J/L to orbit, I/K to zoom. Not the arrow
+/// keys, they still steer the local player underneath. Where it's left is saved with the rest of
+/// the [`CameraSettings`].
+fn overview_camera(
+    mut settings: ResMut<CameraSettings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+    mut moving: Local<bool>,
+) {
+    if settings.mode != CameraMode::Overview {
+        return;
+    }
+
+    // Saved once they let go, rather than every frame a key is held
+    let was_moving = *moving;
+    *moving =
+        keyboard_input.any_pressed([KeyCode::KeyJ, KeyCode::KeyL, KeyCode::KeyI, KeyCode::KeyK]);
+    if was_moving && !*moving {
+        settings.set_changed();
+    }
+
+    let orbit = &mut settings.bypass_change_detection().overview;
+    let dt = time.delta_secs();
+    if keyboard_input.pressed(KeyCode::KeyJ) {
+        orbit.yaw -= dt;
+    }
+    if keyboard_input.pressed(KeyCode::KeyL) {
+        orbit.yaw += dt;
+    }
+    if keyboard_input.pressed(KeyCode::KeyI) {
+        orbit.distance -= SPHERE_RADIUS * dt;
+    }
+    if keyboard_input.pressed(KeyCode::KeyK) {
+        orbit.distance += SPHERE_RADIUS * dt;
+    }
+    orbit.distance = orbit
+        .distance
+        .clamp(SPHERE_RADIUS * 2.0, SPHERE_RADIUS * 10.0);
+
+    **camera = orbit.transform();
+}
+
+/// Follows the local player, or who we're spectating once they're dead. Presentation only, it
+/// runs once per rendered frame so rollbacks don't speed up the gliding.
+#[allow(clippy::too_many_arguments)]
+fn move_camera(
+    local_players: Res<LocalPlayers>,
+    spectate_target: Res<SpectateTarget>,
+    accessibility: Res<AccessibilitySettings>,
+    camera_settings: Res<CameraSettings>,
+    time: Res<Time>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    mut cam_transform: Single<&mut Transform, With<Camera3d>>,
+    players: Query<(&Transform, &Player), Without<Camera3d>>,
+) {
+    if camera_settings.mode == CameraMode::Overview {
+        // Not following anyone, see overview_camera
+        return;
+    }
+
+    // If nobody's picked to spectate yet, the lowest handle still alive
+    let local_transform = players
+        .iter()
+        .find_map(|(transform, p)| local_players.0.contains(&p.handle).then_some(transform))
+        .copied();
+    let spectating = local_transform.is_none();
+
+    let target_transform = local_transform.or_else(|| {
+        players
+            .iter()
+            .find(|(_, p)| Some(p.handle) == **spectate_target)
+            .or_else(|| players.iter().min_by_key(|(_, p)| p.handle))
+            .map(|(transform, _)| *transform)
+    });
+
+    // Fractions of the way to go each frame at 60 fps, whatever the frame rate actually is
+    let glide = |per_frame: f32| 1.0 - ops::powf(1.0 - per_frame, time.delta_secs() * 60.0);
+
+    let Some(player_transform) = target_transform else {
+        // Everyone's dead, like after a double KO. Glide out to look at the whole sphere until
+        // the next round spawns someone to follow.
+        let overview = OverviewOrbit::default().transform();
+        cam_transform.translation = cam_transform
+            .translation
+            .lerp(overview.translation, glide(0.05));
+        cam_transform.look_at(Vec3::ZERO, Vec3::Y);
+        return;
+    };
+
+    let player_pos = player_transform.translation;
+    let player_up = player_pos.normalize_or_zero();
+
+    // The vertical FOV is fixed, so portrait windows see less across than a square one. Back off
+    // until the narrow side covers what it would in a square window. Read every frame so resizes
+    // apply straight away.
+    let aspect = window
+        .map(|window| window.width() / window.height().max(1.0))
+        .filter(|aspect| aspect.is_finite() && *aspect > 0.0)
+        .unwrap_or(1.0);
+    let height = camera_settings.follow_height / aspect.clamp(0.25, 1.0);
+
+    // Position camera almost directly "up" from the player, nudged "back" so look_at has a
+    // direction to work with
+    let backwards = -player_transform.forward();
+    let cam_pos = player_pos + (backwards * 0.01) + (player_up * height);
+
+    cam_transform.translation = if spectating {
+        // Glide over to whoever we're watching instead of cutting
+        cam_transform.translation.lerp(cam_pos, glide(0.15))
+    } else {
+        cam_pos
+    };
+
+    if accessibility.reduced_motion {
+        // Keep whatever is currently "up" on screen instead of turning with the player, only
+        // tilting it as much as needed to stay tangent to the sphere
+        let screen_up = cam_transform.up().as_vec3();
+        let screen_up = (screen_up - player_up * screen_up.dot(player_up))
+            .try_normalize()
+            .unwrap_or(player_transform.forward().as_vec3());
+        cam_transform.look_at(player_pos, screen_up);
+    } else {
+        // Look at the player, keeping the planet's "Up" as the camera's "Up"
+        cam_transform.look_at(player_pos, player_up);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_ggrs::ggrs::{PlayerType, SessionBuilder};
+
+    use super::*;
+    use crate::physics_config::ArenaBand;
+
+    /// What [`step_player`] works on for one player
+    type PlayerState = (Transform, Velocity, Player);
+
+    /// Handle 0 as [`spawn_player`] leaves them
+    fn spawned() -> PlayerState {
+        let spawn = spawn_transform(0, 2);
+        let player = Player {
+            handle: 0,
+            fuel: 100.0,
+            grounded: true,
+            last_trail_pos: spawn.translation,
+            ..default()
+        };
+        (spawn, Velocity::default(), player)
+    }
+
+    /// A frame at the usual rate at the start of the match, with no modifier or wind
+    fn step(physics: &PhysicsConfig) -> MoveStep<'_> {
+        MoveStep {
+            physics,
+            modifier: RoundModifier::None,
+            clock: MatchClock::default(),
+            wind: Wind::default(),
+            speed_multiplier: 1.0,
+            dt: 1.0 / FPS as f32,
+        }
+    }
+
+    /// Steps `state` once per input, all on the same `step`
+    fn run_step(state: &mut PlayerState, inputs: &[Input], step: &MoveStep, handicap: Handicap) {
+        let (transform, vel, player) = state;
+        for input in inputs {
+            step_player(transform, vel, player, *input, handicap, step);
+        }
+    }
+
+    /// Steps `state` once per input, with no modifier, wind or handicap
+    fn run(state: &mut PlayerState, inputs: &[Input], physics: &PhysicsConfig) {
+        run_step(state, inputs, &step(physics), Handicap::None);
+    }
+
+    /// The same checksums peers compare
+    fn checksum(state: &PlayerState) -> [u64; 3] {
+        [
+            checksum_transform(&state.0),
+            checksum_velocity(&state.1),
+            checksum_player(&state.2),
+        ]
+    }
+
+    /// A session with everyone on this machine, like `--synctest`
+    fn session(num_players: usize) -> Session<GameConfig> {
+        let mut builder = SessionBuilder::<GameConfig>::new().with_num_players(num_players);
+        for handle in 0..num_players {
+            builder = builder.add_player(PlayerType::Local, handle).unwrap();
+        }
+        Session::SyncTest(builder.start_synctest_session().unwrap())
+    }
+
+    /// Everything [`check_round_end`] reads, with nobody scored yet and `alive` still going
+    fn round_world(num_players: usize, alive: &[usize]) -> World {
+        let mut world = World::new();
+        world.insert_resource(session(num_players));
+        world.init_resource::<PhysicsConfig>();
+        world.init_resource::<CasualRound>();
+        world.insert_resource(Scores((0..num_players).map(|handle| (handle, 0)).collect()));
+        world.init_resource::<RoundScored>();
+        world.init_resource::<DeathStack>();
+        world.init_resource::<NextState<RollbackState>>();
+        for &handle in alive {
+            world.spawn(Player {
+                handle,
+                ..default()
+            });
+        }
+        world
+    }
+
+    /// Enough of GGRS to run [`spawn_players`] on its own, before anything has spawned
+    fn round_start_app(num_players: usize) -> App {
+        let mut app = App::new();
+        app.add_plugins(GgrsPlugin::<GameConfig>::default())
+            .insert_resource(session(num_players))
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<DeathStack>()
+            .init_resource::<CasualRound>()
+            .init_resource::<RoundScored>()
+            .init_resource::<RoundCountdown>()
+            .insert_resource(GameRng::new(0x5eed));
+        app
+    }
+
+    /// Each frame's input for every handle in a [`synctest_app`], by frame then handle
+    #[derive(Resource)]
+    struct Script {
+        inputs: fn(usize, usize) -> Input,
+        num_players: usize,
+        frames_read: usize,
+    }
+
+    /// What each frame came out as the first time it was simulated, which every resimulation of
+    /// it is checked against
+    #[derive(Resource, Default)]
+    struct Simulated {
+        checksums: HashMap<i32, u64>,
+        resimulated: usize,
+    }
+
+    fn read_script(mut commands: Commands, mut script: ResMut<Script>) {
+        let frame = script.frames_read;
+        script.frames_read += 1;
+        commands.insert_resource(LocalInputs::<GameConfig>(
+            (0..script.num_players)
+                .map(|handle| (handle, (script.inputs)(frame, handle)))
+                .collect(),
+        ));
+    }
+
+    fn record_frame(
+        frame: Res<RollbackFrameCount>,
+        players: Query<(&Transform, &Velocity, &Player)>,
+        trails: Query<(&Transform, &TrailSegment)>,
+        scores: Res<Scores>,
+        clock: Res<MatchClock>,
+        wind: Res<Wind>,
+        mut simulated: ResMut<Simulated>,
+    ) {
+        let mut players: Vec<_> = players
+            .iter()
+            .map(|(transform, vel, player)| {
+                [
+                    checksum_transform(transform),
+                    checksum_velocity(vel),
+                    checksum_player(player),
+                ]
+            })
+            .collect();
+        players.sort();
+        let mut trails: Vec<_> = trails
+            .iter()
+            .map(|(transform, segment)| {
+                [
+                    checksum_transform(transform),
+                    checksum_trail_segment(segment),
+                ]
+            })
+            .collect();
+        trails.sort();
+        let checksum =
+            FixedHasher.hash_one((players, trails, checksum_scores(&scores), *clock, *wind));
+
+        let frame = i32::from(*frame);
+        if let Some(first) = simulated.checksums.get(&frame) {
+            assert_eq!(
+                *first, checksum,
+                "frame {frame} came out different resimulated"
+            );
+            simulated.resimulated += 1;
+        } else {
+            simulated.checksums.insert(frame, checksum);
+        }
+    }
+
+    /// The game's [`SimulationPlugin`] without anything rendered, under a SyncTest session that
+    /// rolls back and plays every frame again. The first update starts a round, then each one
+    /// runs a frame with `inputs`.
+    fn synctest_app(
+        num_players: usize,
+        physics: PhysicsConfig,
+        inputs: fn(usize, usize) -> Input,
+    ) -> App {
+        let mut builder = SessionBuilder::<GameConfig>::new()
+            .with_num_players(num_players)
+            .with_check_distance(2);
+        for handle in 0..num_players {
+            builder = builder.add_player(PlayerType::Local, handle).unwrap();
+        }
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            bevy::state::app::StatesPlugin,
+            AssetPlugin::default(),
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .add_plugins(SimulationPlugin)
+        .insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+            std::time::Duration::from_secs_f64(1.0 / FPS as f64),
+        ))
+        .insert_resource(Session::SyncTest(builder.start_synctest_session().unwrap()))
+        .insert_resource(physics)
+        .insert_resource(Scores((0..num_players).map(|handle| (handle, 0)).collect()))
+        .insert_resource(MatchStats {
+            kills: vec![0; num_players],
+            frames_alive: vec![0; num_players],
+        })
+        .insert_resource(TrailMaterials(vec![default(); num_players]))
+        .insert_resource(Script {
+            inputs,
+            num_players,
+            frames_read: 0,
+        })
+        .init_resource::<Simulated>()
+        .add_systems(ReadInputs, read_script)
+        .add_systems(
+            RollbackUpdate,
+            record_frame
+                .after(shrink_trails)
+                .after(tally_match_stats)
+                .after(tick_match_clock),
+        );
+        app.world_mut()
+            .resource_mut::<NextState<RollbackState>>()
+            .set(RollbackState::InRound);
+        app
+    }
+
+    /// Runs `frames` frames of a [`synctest_app`], and checks some of them really were rolled
+    /// back and played again
+    fn run_synctest(app: &mut App, frames: u32) {
+        for _ in 0..frames {
+            app.update();
+        }
+        assert!(
+            app.world().resource::<Simulated>().resimulated > 0,
+            "nothing was resimulated"
+        );
+    }
+
+    /// Every player and trail segment in `world`, for [`deaths`]
+    fn players_and_trails(
+        world: &mut World,
+    ) -> (Vec<(Transform, Player)>, Vec<(Transform, TrailSegment)>) {
+        let players = world
+            .query::<(&Transform, &Player)>()
+            .iter(world)
+            .map(|(transform, player)| (*transform, player.clone()))
+            .collect();
+        let trails = world
+            .query::<(&Transform, &TrailSegment)>()
+            .iter(world)
+            .map(|(transform, segment)| (*transform, *segment))
+            .collect();
+        (players, trails)
+    }
+
+    /// Runs [`check_round_end`] and returns whether it ended the round
+    fn check_round_end_in(world: &mut World) -> bool {
+        world.run_system_once(check_round_end).unwrap();
+        matches!(
+            world.resource::<NextState<RollbackState>>(),
+            NextState::Pending(RollbackState::RoundEnd)
+        )
+    }
+
+    /// [`find_deaths`] with nobody pressing anything
+    fn deaths(
+        players: &[(Transform, Player)],
+        trails: &[(Transform, TrailSegment)],
+        physics: &PhysicsConfig,
+        frame: i32,
+    ) -> Vec<Death> {
+        let players: Vec<_> = players
+            .iter()
+            .map(|(transform, player)| (Entity::PLACEHOLDER, transform, player))
+            .collect();
+        let trails: Vec<_> = trails
+            .iter()
+            .map(|(transform, segment)| (transform, segment))
+            .collect();
+        let inputs = vec![(Input::default(), InputStatus::Confirmed); 8];
+        find_deaths(
+            &players,
+            &trails,
+            &inputs,
+            physics,
+            RoundModifier::None,
+            frame,
+            frame,
+        )
+        .into_iter()
+        .map(|(_, death)| death)
+        .collect()
+    }
+
+    #[test]
+    fn dash_lasts_and_recharges_in_exact_frames() {
+        let physics = PhysicsConfig::default();
+        let mut state = spawned();
+        let dash = Input::default().with_dash(true);
+
+        // Held the whole time, so each dash starts the first frame it's allowed to
+        let mut started = Vec::new();
+        let mut dashing_frames = 0;
+        for frame in 0..=DASH_COOLDOWN_FRAMES * 2 {
+            run(&mut state, &[dash], &physics);
+            if state.2.dashing == DASH_LENGTH_FRAMES - 1 {
+                started.push(frame);
+            }
+            if frame < DASH_COOLDOWN_FRAMES && state.2.dashing != 0 {
+                dashing_frames += 1;
+            }
+        }
+
+        assert_eq!(started, [0, DASH_COOLDOWN_FRAMES, DASH_COOLDOWN_FRAMES * 2]);
+        assert_eq!(dashing_frames, DASH_LENGTH_FRAMES - 1);
+    }
+
+    /// Dash held the whole time, so rollbacks land in the middle of dashes and cooldowns
+    #[test]
+    fn dash_resimulates_exactly() {
+        let mut app = synctest_app(1, PhysicsConfig::default(), |frame, _| {
+            Input::default()
+                .with_dash(true)
+                .with_left(frame % 40 < 10)
+                .with_jump(frame == DASH_COOLDOWN_FRAMES as usize + 5)
+        });
+        run_synctest(&mut app, DASH_COOLDOWN_FRAMES + DASH_LENGTH_FRAMES / 2);
+
+        let world = app.world_mut();
+        let player = world.query::<&Player>().single(world).unwrap();
+        assert!(player.dashing > 0, "the second dash never started");
+    }
+
+    #[test]
+    fn spawn_grace_covers_every_frame_of_it() {
+        let physics = PhysicsConfig {
+            spawn_grace_frames: 10,
+            player_collision: PlayerCollision::BothDie,
+            ..default()
+        };
+
+        // Spawned right on top of each other and someone else's trail
+        let spawn = spawn_transform(0, 2);
+        let mut players = [0, 1].map(|handle| {
+            let player = Player {
+                handle,
+                spawn_grace: physics.spawn_grace_frames,
+                ..default()
+            };
+            (spawn, player)
+        });
+        let trail = TrailSegment {
+            owner: 2,
+            ..default()
+        };
+
+        // Counted down after each check, like check_collisions does
+        for frame in 0..physics.spawn_grace_frames as i32 {
+            assert!(
+                deaths(&players, &[(spawn, trail)], &physics, frame).is_empty(),
+                "died {frame} frames after spawning"
+            );
+            for (_, player) in &mut players {
+                player.spawn_grace -= 1;
+            }
+        }
+
+        let handles: Vec<usize> = deaths(&players, &[(spawn, trail)], &physics, 10)
+            .iter()
+            .map(|death| death.handle)
+            .collect();
+        assert_eq!(handles, [0, 1]);
+    }
+
+    /// Turning on and off faster than the drift catches up, so every snapshot GGRS restores is
+    /// partway through a turn
+    #[test]
+    fn drift_resimulates_exactly() {
+        let physics = PhysicsConfig {
+            drift: true,
+            ..default()
+        };
+        let mut app = synctest_app(1, physics, |frame, _| {
+            Input::default()
+                .with_dash(frame % 100 == 0)
+                .with_left(frame % 4 < 2)
+        });
+        run_synctest(&mut app, FPS as u32 * 2);
+
+        let full_turn = PI * TURN_SPEED;
+        let world = app.world_mut();
+        let player = world.query::<&Player>().single(world).unwrap();
+        assert!(player.turn_velocity > 0.0 && player.turn_velocity < full_turn);
+    }
+
+    #[test]
+    fn segments_collide_along_their_whole_length() {
+        let physics = PhysicsConfig::default();
+
+        // Left by a dash that covered far more than the usual spacing in one frame, lying along Y
+        let segment = TrailSegment {
+            owner: 1,
+            length: 1.0,
+            ..default()
+        };
+        let player = Player {
+            handle: 0,
+            ..default()
+        };
+
+        // Near the end, well clear of the middle the spacing alone would cover
+        let near_end = Transform::from_xyz(0.0, 0.45, 0.0);
+        let killers: Vec<usize> = deaths(
+            &[(near_end, player)],
+            &[(Transform::default(), segment)],
+            &physics,
+            0,
+        )
+        .iter()
+        .map(|death| death.killer)
+        .collect();
+        assert_eq!(killers, [1]);
+    }
+
+    #[test]
+    fn peers_shuffle_spawns_the_same() {
+        let physics = PhysicsConfig {
+            shuffle_spawns: true,
+            ..default()
+        };
+
+        // Every peer starts the match with the same seed
+        let mut ours = GameRng::new(0x5eed);
+        let mut theirs = GameRng::new(0x5eed);
+        let mut changed = false;
+        for _ in 0..10 {
+            let slots = spawn_slots(6, &physics, &mut ours);
+            assert_eq!(slots, spawn_slots(6, &physics, &mut theirs));
+
+            let mut sorted = slots.clone();
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 3, 4, 5]);
+            changed |= slots != sorted;
+        }
+        assert!(changed, "spawns were never shuffled");
+
+        // Fixed spawns don't use the rng at all
+        let fixed = PhysicsConfig::default();
+        let before = FixedHasher.hash_one(ours);
+        assert_eq!(spawn_slots(6, &fixed, &mut ours), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(FixedHasher.hash_one(ours), before);
+    }
+
+    /// A few seconds of everything at once, turning, jumping, hovering and dashing in wind
+    fn fixed_input_checksum() -> [u64; 3] {
+        let physics = PhysicsConfig {
+            wind_strength: 1.0,
+            drift: true,
+            ..default()
+        };
+        let mut state = spawned();
+        for frame in 0..FPS as u32 * 5 {
+            let input = Input::default()
+                .with_left(frame % 90 < 30)
+                .with_right(frame % 120 > 100)
+                .with_jump(frame % 70 == 0)
+                .with_hover(frame % 70 > 20)
+                .with_separate_hover(true)
+                .with_dash(frame % 200 == 50);
+            let step = MoveStep {
+                clock: MatchClock { frames: frame },
+                wind: Wind { seed: 1234 },
+                ..step(&physics)
+            };
+            run_step(&mut state, &[input], &step, Handicap::None);
+        }
+        checksum(&state)
+    }
+
+    /// What [`fixed_input_checksum`] comes out as with `--features deterministic-math`, the same
+    /// on every platform. After changing the simulation on purpose, run the tests with `BLESS=1`
+    /// set to write the new one, and commit it.
+    #[cfg(feature = "deterministic-math")]
+    const GOLDEN_CHECKSUM: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/golden/fixed_input_checksum.txt"
+    );
+
+    /// Both math paths have to be deterministic on their own, and with `deterministic-math` the
+    /// result is pinned down so every platform can be checked against the same value
+    #[test]
+    fn fixed_inputs_simulate_the_same_every_time() {
+        let checksum = fixed_input_checksum();
+        assert_eq!(checksum, fixed_input_checksum());
+
+        #[cfg(feature = "deterministic-math")]
+        {
+            let checksum = checksum.map(|part| format!("{part:016x}")).join(" ");
+            if std::env::var_os("BLESS").is_some() {
+                let path = std::path::Path::new(GOLDEN_CHECKSUM);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, &checksum).unwrap();
+            }
+            let golden = std::fs::read_to_string(GOLDEN_CHECKSUM).unwrap_or_else(|_| {
+                panic!("no golden checksum at {GOLDEN_CHECKSUM}, run with BLESS=1 to write it")
+            });
+            assert_eq!(
+                golden.trim(),
+                checksum,
+                "the simulation changed, run with BLESS=1 if that was on purpose"
+            );
+        }
+    }
+
+    #[test]
+    fn last_stand_is_exactly_the_last_two() {
+        let physics = PhysicsConfig {
+            last_stand_speed: 1.2,
+            ..default()
+        };
+
+        // Players dying one by one, then someone respawning
+        let boosted: Vec<bool> = [4, 3, 2, 1, 2, 3]
+            .into_iter()
+            .map(|alive| is_last_stand(alive, 4, &physics))
+            .collect();
+        assert_eq!(boosted, [false, false, true, false, true, false]);
+
+        // A two player match starts with two, there's nothing to boost
+        assert!(!is_last_stand(2, 2, &physics));
+        assert!(!is_last_stand(2, 4, &PhysicsConfig::default()));
+    }
+
+    /// Whoever `handle` ran into on `frame`
+    fn death(handle: usize, frame: i32) -> Death {
+        Death {
+            handle,
+            killer: handle,
+            frame,
+            running_frame: frame,
+            trail_frame: None,
+            position: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn dead_trails_decay_after_the_delay() {
+        // Player 0 died last round too, then player 1 died this round before them
+        let death_log = DeathLog(vec![death(0, 10), death(1, 90), death(0, 100)]);
+
+        assert!(!dead_trail_decayed(0, &death_log, 129, 30));
+        assert!(dead_trail_decayed(0, &death_log, 130, 30));
+        assert!(dead_trail_decayed(1, &death_log, 120, 30));
+
+        // Never died, so the trail stays
+        assert!(!dead_trail_decayed(2, &death_log, 1000, 30));
+    }
+
+    /// Trails left before a pause are as old afterwards as they were when it started
+    #[test]
+    fn net_pauses_dont_age_trails() {
+        let physics = PhysicsConfig {
+            pauses_per_player: 1,
+            pause_frames: FPS as u32,
+            trail_shrink_frames: Some(FPS as u32),
+            ..default()
+        };
+        let mut app = synctest_app(1, physics, |frame, _| {
+            Input::default().with_pause(frame == 30)
+        });
+        // Half a second, paused for a second, then a few frames more
+        run_synctest(&mut app, 30 + FPS as u32 + 10);
+
+        let world = app.world_mut();
+        assert_eq!(world.resource::<NetPause>().paused_frames, FPS as u32);
+        let oldest = world
+            .query::<&TrailSegment>()
+            .iter(world)
+            .map(|segment| segment.created_frame)
+            .min();
+        assert!(
+            oldest.is_some_and(|frame| frame < 30),
+            "trails from before the pause are gone"
+        );
+    }
+
+    #[test]
+    fn path_over_the_sphere_doesnt_depend_on_frame_rate() {
+        let physics = PhysicsConfig::default();
+        let turning = Input::default().with_left(true);
+
+        // One second of turning left at each rate
+        let end = |fps: usize| {
+            let step = MoveStep {
+                dt: 1.0 / fps as f32,
+                ..step(&physics)
+            };
+            let mut state = spawned();
+            run_step(&mut state, &vec![turning; fps], &step, Handicap::None);
+            state.0
+        };
+
+        let (slow, fast) = (end(30), end(120));
+        assert!(
+            slow.translation.distance(fast.translation) < 1e-3,
+            "{} vs {}",
+            slow.translation,
+            fast.translation
+        );
+        assert!(slow.forward().dot(*fast.forward()) > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn altitude_stays_under_the_clamp() {
+        // Everything that goes up, held the whole time
+        let input = Input::default()
+            .with_jump(true)
+            .with_hover(true)
+            .with_separate_hover(true)
+            .with_dash(true);
+
+        for max_altitude in [0.5, PhysicsConfig::default().max_altitude] {
+            for modifier in [RoundModifier::None, RoundModifier::LowGravity] {
+                let physics = PhysicsConfig {
+                    max_altitude,
+                    ..default()
+                };
+                let step = MoveStep {
+                    modifier,
+                    ..step(&physics)
+                };
+
+                let mut state = spawned();
+                for _ in 0..FPS * 10 {
+                    run_step(&mut state, &[input], &step, Handicap::None);
+                    let altitude = state.0.translation.length() - SPHERE_RADIUS;
+                    assert!(
+                        altitude <= max_altitude + 1e-4,
+                        "{altitude} above the surface with {modifier:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Each flag's builder and accessor, with the bit it's documented to use
+    #[allow(clippy::type_complexity)]
+    const INPUT_FLAGS: [(fn(Input, bool) -> Input, fn(Input) -> bool, u16); 10] = [
+        (Input::with_jump, Input::is_jump, 1 << 0),
+        (Input::with_left, Input::is_left, 1 << 1),
+        (Input::with_right, Input::is_right, 1 << 2),
+        (Input::with_dash, Input::is_dash, 1 << 3),
+        (Input::with_hover, Input::is_hover, 1 << 4),
+        (Input::with_continue, Input::is_continue, 1 << 5),
+        (Input::with_forfeit, Input::is_forfeit, 1 << 6),
+        (Input::with_brake, Input::is_brake, 1 << 9),
+        (Input::with_pause, Input::is_pause, 1 << 10),
+        (
+            Input::with_separate_hover,
+            Input::is_separate_hover,
+            1 << 11,
+        ),
+    ];
+
+    #[test]
+    fn input_flags_use_their_own_bits() {
+        for (with, is, bit) in INPUT_FLAGS {
+            let pressed = with(Input::default(), true);
+            assert_eq!(u16::from(pressed), bit);
+            assert!(is(pressed));
+            assert!(!is(Input::default()));
+
+            // Only ever touches its own bit
+            let everything = Input::from(u16::MAX);
+            assert!(is(everything));
+            assert_eq!(u16::from(with(everything, false)), u16::MAX & !bit);
+            assert!(!is(with(everything, false)));
+
+            for (_, other, other_bit) in INPUT_FLAGS {
+                if other_bit != bit {
+                    assert!(!other(pressed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn input_round_trips_through_u16() {
+        for bits in [0, 1, 0b101_1010_0110, u16::MAX] {
+            assert_eq!(u16::from(Input::from(bits)), bits);
+        }
+    }
+
+    #[test]
+    fn practicing_alone_goes_on_until_they_crash() {
+        let mut world = round_world(1, &[0]);
+        assert!(!check_round_end_in(&mut world));
+
+        let player = world.query::<(Entity, &Player)>().single(&world).unwrap().0;
+        world.despawn(player);
+        assert!(check_round_end_in(&mut world));
+    }
+
+    #[test]
+    fn rounds_end_with_one_left() {
+        assert!(!check_round_end_in(&mut round_world(2, &[0, 1])));
+        assert!(check_round_end_in(&mut round_world(2, &[1])));
+    }
+
+    #[test]
+    fn head_on_collisions_come_out_the_same_either_way_round() {
+        let spawn = spawn_transform(0, 2);
+        let a = Player {
+            handle: 0,
+            dashing: 5,
+            ..default()
+        };
+        let b = Player {
+            handle: 1,
+            ..default()
+        };
+        // Just under touching, facing each other
+        let a_at = spawn;
+        let b_at = Transform::from_translation(spawn.translation + spawn.forward() * 0.3)
+            .looking_to(-spawn.forward(), spawn.up());
+
+        let outcome = |rule, players: &[(Transform, Player)]| {
+            let physics = PhysicsConfig {
+                player_collision: rule,
+                ..default()
+            };
+            deaths(players, &[], &physics, 0)
+                .iter()
+                .map(|death| (death.handle, death.killer))
+                .collect::<Vec<_>>()
+        };
+
+        // Every peer sees the same thing whatever order their query happens to be in
+        let ours = [(a_at, a.clone()), (b_at, b.clone())];
+        let theirs = [(b_at, b), (a_at, a)];
+        for (rule, expected) in [
+            (PlayerCollision::BothDie, vec![(0, 1), (1, 0)]),
+            (PlayerCollision::DashWins, vec![(1, 0)]),
+            (PlayerCollision::Off, vec![]),
+        ] {
+            assert_eq!(outcome(rule, &ours), expected, "{rule:?}");
+            assert_eq!(outcome(rule, &theirs), expected, "{rule:?}");
+        }
+    }
+
+    /// Both players crash into each other on the first frame, and share the round's win
+    #[test]
+    fn head_on_double_kos_share_the_win() {
+        /// Puts handle 1 just in front of handle 0, facing them
+        fn face_off(mut players: Query<(&mut Transform, &mut Player)>) {
+            let spawn = spawn_transform(0, 2);
+            for (mut transform, mut player) in &mut players {
+                *transform = if player.handle == 0 {
+                    spawn
+                } else {
+                    Transform::from_translation(spawn.translation + spawn.forward() * 0.3)
+                        .looking_to(-spawn.forward(), spawn.up())
+                };
+                player.last_trail_pos = transform.translation;
+            }
+        }
+
+        let physics = PhysicsConfig {
+            player_collision: PlayerCollision::BothDie,
+            ..default()
+        };
+        let mut app = synctest_app(2, physics, |_, _| Input::default());
+        app.add_systems(
+            OnEnter(RollbackState::InRound),
+            face_off.after(spawn_players),
+        );
+        // Well short of the next round starting
+        run_synctest(&mut app, 10);
+
+        let world = app.world_mut();
+        let num_alive = world.query::<&Player>().iter(world).count();
+        let scores = world.resource::<Scores>();
+        assert_eq!([scores[&0], scores[&1]], [1, 1]);
+        assert!(world.resource::<DeathStack>().is_double_ko(num_alive));
+    }
+
+    /// Everyone but handle 0 forfeits, leaving the first round's trails over every spawn. The
+    /// next round starts on top of them, with rollbacks re-running the transition into it.
+    #[test]
+    fn last_rounds_trails_are_gone_before_anyone_can_hit_them() {
+        let mut app = synctest_app(4, PhysicsConfig::default(), |frame, handle| {
+            Input::default().with_forfeit(handle != 0 && frame == 20)
+        });
+        // Through the pause between rounds and half a second into the next one
+        run_synctest(&mut app, 20 + FPS as u32 * 3 / 2);
+
+        let world = app.world_mut();
+        assert_eq!(
+            *world.resource::<State<RollbackState>>().get(),
+            RollbackState::InRound
+        );
+        assert_eq!(world.query::<&Player>().iter(world).count(), 4);
+        let died: Vec<usize> = world
+            .resource::<DeathLog>()
+            .iter()
+            .map(|death| death.handle)
+            .collect();
+        assert_eq!(died, [1, 2, 3]);
+    }
+
+    /// Wind only depends on the clock and the seed drawn when the round starts, both rolled back,
+    /// so resimulated frames blow players the same way
+    #[test]
+    fn wind_resimulates_exactly() {
+        let end = |wind_strength| {
+            let physics = PhysicsConfig {
+                wind_strength,
+                ..default()
+            };
+            let mut app = synctest_app(1, physics, |_, _| Input::default());
+            run_synctest(&mut app, FPS as u32 * 2);
+
+            let world = app.world_mut();
+            let mut players = world.query_filtered::<&Transform, With<Player>>();
+            players.single(world).unwrap().translation
+        };
+        assert_ne!(end(1.0), end(0.0), "the wind never blew");
+    }
+
+    /// A full dash by distance covers that arc of the surface, however long the dash lasts
+    #[test]
+    fn distance_dash_covers_its_arc() {
+        let physics = PhysicsConfig {
+            dash_distance: Some(12.0),
+            ..default()
+        };
+        let mut state = spawned();
+        let start = state.0.translation;
+
+        let mut inputs = vec![Input::default(); DASH_LENGTH_FRAMES as usize - 1];
+        inputs[0] = Input::default().with_dash(true);
+        run(&mut state, &inputs, &physics);
+
+        let arc = start.angle_between(state.0.translation) * SPHERE_RADIUS;
+        assert!((arc - 12.0).abs() < 0.05, "dashed {arc} instead of 12");
+    }
+
+    #[test]
+    fn most_votes_win_and_ties_go_to_the_first_modifier() {
+        let votes = |votes: &[RoundModifier]| ModifierVotes {
+            votes: votes.iter().copied().enumerate().collect(),
+            held: Vec::new(),
+        };
+        assert_eq!(votes(&[]).winner(), RoundModifier::None);
+        assert_eq!(
+            votes(&[
+                RoundModifier::FastDash,
+                RoundModifier::ThickTrails,
+                RoundModifier::FastDash,
+            ])
+            .winner(),
+            RoundModifier::FastDash
+        );
+        assert_eq!(
+            votes(&[RoundModifier::FastDash, RoundModifier::LowGravity]).winner(),
+            RoundModifier::LowGravity
+        );
+        // Voting for no modifier counts the same as any other vote
+        assert_eq!(
+            votes(&[RoundModifier::ThickTrails, RoundModifier::None]).winner(),
+            RoundModifier::None
+        );
+    }
+
+    #[test]
+    fn no_modifier_vote_without_time_to_vote() {
+        let mut physics = PhysicsConfig {
+            modifier_vote: true,
+            ..default()
+        };
+        assert!(physics.modifier_voting());
+        physics.round_transition = RoundTransition::Instant;
+        assert!(!physics.modifier_voting());
+    }
+
+    /// On the surface, upright, and facing along it rather than into it
+    fn assert_on_surface(spawn: Transform) {
+        let up = spawn.translation.normalize();
+        assert!((spawn.translation.length() - SPHERE_RADIUS).abs() < 1e-4);
+        assert!(spawn.up().dot(up) > 0.9999, "{spawn:?} isn't upright");
+        assert!(
+            spawn.forward().dot(up).abs() < 1e-4,
+            "{spawn:?} faces off the surface"
+        );
+    }
+
+    #[test]
+    fn hand_picked_slots_spawn_facing_along_the_surface() {
+        for handle in 0..SLOT_INFO.len() {
+            assert_on_surface(spawn_transform(handle, SLOT_INFO.len()));
+        }
+    }
+
+    #[test]
+    fn every_spawn_faces_along_the_surface() {
+        for num_players in 1..=crate::lobby_config::MAX_PLAYERS {
+            for handle in 0..num_players {
+                assert_on_surface(spawn_transform(handle, num_players));
+            }
+        }
+    }
+
+    #[test]
+    fn scores_rank_highest_first_with_ties_in_handle_order() {
+        let scores = HashMap::from([(0, 1), (1, 3), (2, 1), (3, 0), (4, 3)]);
+        assert_eq!(
+            ranked_scores(&scores),
+            [(1, 3), (4, 3), (0, 1), (2, 1), (3, 0)]
+        );
+        assert_eq!(
+            scoreboard_text(&scores, ScoreboardOrder::Score, &PhysicsConfig::default()),
+            "P2: 3 - P5: 3 - P1: 1 - P3: 1 - P4: 0"
+        );
+    }
+
+    /// Rollbacks re-run the frame the round ends on, and the ones either side of it, without
+    /// scoring the round again
+    #[test]
+    fn rounds_score_once_across_a_rollback() {
+        let mut app = synctest_app(3, PhysicsConfig::default(), |frame, handle| {
+            Input::default().with_forfeit((handle, frame) == (0, 10) || (handle, frame) == (2, 20))
+        });
+        // Well short of the next round starting
+        run_synctest(&mut app, 40);
+
+        let scores = app.world().resource::<Scores>();
+        assert_eq!([scores[&0], scores[&1], scores[&2]], [0, 2, 1]);
+    }
+
+    #[test]
+    fn analog_turns_round_trip_through_the_input() {
+        for (x, strength) in [
+            (0.3, 0.25),
+            (0.5, 0.5),
+            (-0.7, 0.75),
+            (1.0, 1.0),
+            (-2.0, 1.0),
+        ] {
+            let input = Input::from(u16::from(Input::default().with_analog_turn(x)));
+            assert_eq!(input.turn_strength(), strength, "stick at {x}");
+            assert_eq!(input.is_left(), x < 0.0);
+            assert_eq!(input.is_right(), x > 0.0);
+            // Already on a step, so it quantizes to exactly the same input again
+            assert_eq!(
+                Input::default().with_analog_turn(x.signum() * strength),
+                input
+            );
+        }
+
+        let centered = Input::default().with_left(true).with_analog_turn(0.1);
+        assert!(!centered.is_left() && !centered.is_right());
+    }
+
+    /// Dash timers are paused between rounds, so whatever was left of them mustn't carry over
+    #[test]
+    fn rounds_start_with_dash_ready() {
+        let mut app = round_start_app(2);
+        let world = app.world_mut();
+        world.spawn((
+            spawn_transform(0, 2),
+            Player {
+                handle: 0,
+                dashing: 3,
+                dash_cooldown: DASH_COOLDOWN_FRAMES - 1,
+                ..default()
+            },
+        ));
+
+        world.run_system_once(spawn_players).unwrap();
+
+        let physics = PhysicsConfig::default();
+        let (players, _) = players_and_trails(world);
+        assert_eq!(players.len(), 2);
+        for (transform, player) in players {
+            assert_eq!((player.dashing, player.dash_cooldown), (0, 0));
+            let mut state = (transform, Velocity::default(), player);
+            run(&mut state, &[Input::default().with_dash(true)], &physics);
+            assert_eq!(state.2.dashing, DASH_LENGTH_FRAMES - 1);
+        }
+    }
+
+    #[test]
+    fn braking_never_goes_below_the_minimum_speed() {
+        let brake = vec![Input::default().with_brake(true); FPS];
+        let braked_for_a_second = |physics: &PhysicsConfig| {
+            let mut state = spawned();
+            let start = state.0.translation;
+            run(&mut state, &brake, physics);
+            start.angle_between(state.0.translation) * SPHERE_RADIUS
+        };
+
+        let physics = PhysicsConfig {
+            brake_factor: 0.1,
+            min_move_speed: 1.5,
+            ..default()
+        };
+        let moved = braked_for_a_second(&physics);
+        assert!((moved - 1.5).abs() < 0.01, "moved {moved} braking");
+
+        // Modes that allow stopping can
+        let physics = PhysicsConfig {
+            brake_factor: 0.0,
+            min_move_speed: 0.0,
+            ..default()
+        };
+        assert!(braked_for_a_second(&physics) < 1e-3);
+    }
+
+    /// Everyone dying on the same frame comes out in handle order, whatever order the query
+    /// happened to find them in
+    #[test]
+    fn simultaneous_deaths_come_out_in_handle_order() {
+        let on_trails: Vec<_> = (0..3)
+            .map(|handle| {
+                let at = spawn_transform(handle, 3);
+                let player = Player {
+                    handle,
+                    ..default()
+                };
+                let segment = TrailSegment {
+                    owner: (handle + 1) % 3,
+                    length: 1.0,
+                    ..default()
+                };
+                ((at, player), (at, segment))
+            })
+            .collect();
+        let (mut players, mut trails): (Vec<_>, Vec<_>) = on_trails.into_iter().unzip();
+
+        let physics = PhysicsConfig::default();
+        let forwards = deaths(&players, &trails, &physics, 0);
+        players.reverse();
+        trails.reverse();
+        let backwards = deaths(&players, &trails, &physics, 0);
+
+        let handles: Vec<usize> = forwards.iter().map(|death| death.handle).collect();
+        assert_eq!(handles, [0, 1, 2]);
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn fresh_trails_only_spare_their_owner() {
+        let at = spawn_transform(0, 2);
+        let player = |handle| Player {
+            handle,
+            ..default()
+        };
+        let fresh = TrailSegment {
+            owner: 0,
+            created_frame: 10,
+            length: 1.0,
+            ..default()
+        };
+        let players = [(at, player(0)), (at, player(1))];
+        let trails = [(at, fresh)];
+        let physics = PhysicsConfig::default();
+
+        let died = |frame| -> Vec<(usize, usize)> {
+            deaths(&players, &trails, &physics, frame)
+                .iter()
+                .map(|death| (death.handle, death.killer))
+                .collect()
+        };
+        assert_eq!(died(10), [(1, 0)]);
+        assert_eq!(died(10 + MIN_TRAIL_LIFE_FRAMES), [(0, 0), (1, 0)]);
+    }
+
+    /// Everyone draws from [`GameRng`] the same way however the last round ended, so peers that
+    /// roll back across the start of a round stay in step
+    #[test]
+    fn round_start_draws_dont_depend_on_who_was_left() {
+        let after_round_start = |leftover: usize| {
+            let mut app = round_start_app(4);
+            app.insert_resource(PhysicsConfig {
+                shuffle_spawns: true,
+                wind_strength: 1.0,
+                ..default()
+            });
+            let world = app.world_mut();
+            for handle in 0..leftover {
+                world.spawn((
+                    spawn_transform(handle, 4),
+                    Player {
+                        handle,
+                        ..default()
+                    },
+                ));
+            }
+
+            world.run_system_once(spawn_players).unwrap();
+
+            let mut spawns: Vec<(usize, [u32; 3])> = world
+                .query::<(&Transform, &Player)>()
+                .iter(world)
+                .map(|(transform, player)| {
+                    (
+                        player.handle,
+                        transform.translation.to_array().map(f32::to_bits),
+                    )
+                })
+                .collect();
+            spawns.sort();
+            (
+                FixedHasher.hash_one(world.resource::<GameRng>()),
+                world.resource::<Wind>().seed,
+                spawns,
+            )
+        };
+
+        assert_eq!(after_round_start(0), after_round_start(1));
+        assert_eq!(after_round_start(1), after_round_start(3));
+    }
+
+    #[test]
+    fn handicaps_only_help_with_their_own_thing() {
+        for handicap in Handicap::ALL {
+            let helped = [
+                handicap.speed() > 1.0,
+                handicap.fuel_regen() > 1.0,
+                handicap.dash_frames() > DASH_LENGTH_FRAMES,
+            ];
+            let expected = [
+                handicap == Handicap::Speed,
+                handicap == Handicap::Fuel,
+                handicap == Handicap::Dash,
+            ];
+            assert_eq!(helped, expected, "{handicap:?}");
+        }
+
+        // Dash frames are whole frames, a longer dash really lasts longer in the simulation
+        let physics = PhysicsConfig::default();
+        let mut state = spawned();
+        let dash = Input::default().with_dash(true);
+        run_step(&mut state, &[dash], &step(&physics), Handicap::Dash);
+        assert_eq!(state.2.dashing, Handicap::Dash.dash_frames() - 1);
+    }
+
+    #[test]
+    fn handles_without_a_handicap_get_none() {
+        let handicaps = Handicaps(vec![Handicap::Fuel, Handicap::Dash]);
+        assert_eq!(handicaps.get(0), Handicap::Fuel);
+        assert_eq!(handicaps.get(1), Handicap::Dash);
+        assert_eq!(handicaps.get(2), Handicap::None);
+        assert_eq!(Handicaps::default().get(0), Handicap::None);
+    }
+
+    #[test]
+    fn mvp_goes_to_kills_then_time_alive_then_lowest_handle() {
+        let stats = |kills: &[u32], frames_alive: &[u32]| MatchStats {
+            kills: kills.to_vec(),
+            frames_alive: frames_alive.to_vec(),
+        };
+        assert_eq!(stats(&[0, 0, 0], &[0, 0, 0]).mvp(), None);
+        assert_eq!(stats(&[1, 3, 2], &[900, 10, 500]).mvp(), Some(1));
+        assert_eq!(stats(&[2, 2, 1], &[300, 400, 900]).mvp(), Some(1));
+        assert_eq!(stats(&[2, 0, 2], &[300, 900, 300]).mvp(), Some(0));
+        // Nobody got a kill, so whoever lasted longest
+        assert_eq!(stats(&[0, 0, 0], &[5, 7, 7]).mvp(), Some(1));
+    }
+
+    #[test]
+    fn comeback_goes_to_whoever_is_last() {
+        assert_eq!(comeback_candidate(&HashMap::from([(0, 2), (1, 2)])), None);
+        assert_eq!(
+            comeback_candidate(&HashMap::from([(0, 3), (1, 0), (2, 1)])),
+            Some(1)
+        );
+        // Tied for last, the highest handle like the scoreboard's ranking
+        assert_eq!(
+            comeback_candidate(&HashMap::from([(0, 0), (1, 3), (2, 0)])),
+            Some(2)
+        );
+
+        // Winning the round from last place earns the bonus on top
+        let mut world = round_world(3, &[2]);
+        world.insert_resource(PhysicsConfig {
+            comeback_bonus: 1,
+            ..default()
+        });
+        world.insert_resource(Scores(HashMap::from([(0, 4), (1, 2), (2, 0)])));
+        world.insert_resource(DeathStack(vec![vec![0], vec![1]]));
+        assert!(check_round_end_in(&mut world));
+        assert_eq!(world.resource::<Scores>()[&2], 3);
+    }
+
+    #[test]
+    fn input_round_trips_through_serde() {
+        let inputs = [
+            Input::default(),
+            Input::default().with_jump(true).with_brake(true),
+            Input::default().with_analog_turn(-0.5).with_dash(true),
+            Input::from(u16::MAX),
+        ];
+        for input in inputs {
+            let json = serde_json::to_string(&input).unwrap();
+            assert_eq!(serde_json::from_str::<Input>(&json).unwrap(), input);
+        }
+    }
+
+    /// Inputs go in through [`ReadInputs`] like [`read_local_inputs`] and come out of
+    /// [`PlayerInputs`] unchanged on the frame they were read for, resimulations included
+    #[test]
+    fn inputs_reach_the_simulation_frame_for_frame() {
+        #[derive(Resource, Default)]
+        struct Sent(Vec<Input>);
+
+        #[derive(Resource, Default)]
+        struct Received(HashMap<i32, Input>);
+
+        fn feed(mut commands: Commands, mut sent: ResMut<Sent>) {
+            let frame = sent.0.len();
+            let input = Input::default()
+                .with_dash(frame % 5 == 1)
+                .with_brake(frame % 4 == 3);
+            let input = if frame % 7 == 2 {
+                input.with_analog_turn(0.5)
+            } else {
+                input.with_left(frame % 3 == 0)
+            };
+            sent.0.push(input);
+            commands.insert_resource(LocalInputs::<GameConfig>(HashMap::from([(0, input)])));
+        }
+
+        fn record(
+            inputs: Res<PlayerInputs<GameConfig>>,
+            frame: Res<RollbackFrameCount>,
+            mut received: ResMut<Received>,
+        ) {
+            received.0.insert(i32::from(*frame), inputs[0].0);
+        }
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GgrsPlugin::<GameConfig>::default()))
+            .insert_resource(RollbackFrameRate(FPS))
+            .insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+                std::time::Duration::from_secs_f64(1.0 / FPS as f64),
+            ))
+            .insert_resource(session(1))
+            .init_resource::<Sent>()
+            .init_resource::<Received>()
+            .add_systems(ReadInputs, feed)
+            .add_systems(GgrsSchedule, record);
+        for _ in 0..FPS {
+            app.update();
+        }
+
+        let sent = &app.world().resource::<Sent>().0;
+        let received = &app.world().resource::<Received>().0;
+        assert!(
+            received.len() > FPS / 2,
+            "only ran {} frames",
+            received.len()
+        );
+        for (frame, input) in received {
+            assert_eq!(*input, sent[*frame as usize], "frame {frame}");
+        }
+    }
+
+    #[test]
+    fn grounded_holds_steady_between_the_thresholds() {
+        // Further out than landing allows, but not far enough out to count as leaving
+        let between = Vec3::Y
+            * (SPHERE_RADIUS_SQ + (GROUND_ENTER_EPSILON + GROUND_EXIT_EPSILON) / 2.0).sqrt();
+        let surface = Vec3::Y * SPHERE_RADIUS;
+        let above = Vec3::Y * (SPHERE_RADIUS_SQ + GROUND_EXIT_EPSILON * 2.0).sqrt();
+
+        let mut player = Player {
+            grounded: true,
+            ..default()
+        };
+        for _ in 0..10 {
+            assert!(player.update_grounded(between, 0.0));
+        }
+        assert!(!player.update_grounded(above, 0.0));
+        for _ in 0..10 {
+            assert!(!player.update_grounded(between, -1.0));
+        }
+        assert!(player.update_grounded(surface, -1.0));
+
+        // Jumping off leaves the ground straight away, wherever they are
+        assert!(!player.update_grounded(surface, 1.0));
+    }
+
+    /// [`round_start_app`] just after [`spawn_players`], with everyone scored
+    #[cfg(debug_assertions)]
+    fn started_round() -> App {
+        let mut app = round_start_app(3);
+        app.insert_resource(Scores((0..3).map(|handle| (handle, 0)).collect()));
+        app.world_mut().run_system_once(spawn_players).unwrap();
+        app
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn fresh_rounds_pass_the_round_start_check() {
+        let mut app = started_round();
+        app.world_mut().run_system_once(check_round_start).unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "round started with leftover trails")]
+    fn leftover_trails_fail_the_round_start_check() {
+        let mut app = started_round();
+        let world = app.world_mut();
+        world.spawn((Transform::default(), TrailSegment::default()));
+        world.run_system_once(check_round_start).unwrap();
+    }
+
+    #[test]
+    fn upside_down_arena_bands_are_put_right() {
+        let physics = PhysicsConfig {
+            arena_band: Some(ArenaBand {
+                min_latitude: 120.0,
+                max_latitude: -30.0,
+                edge: ArenaEdge::Kill,
+            }),
+            ..default()
+        };
+        let band = physics.arena_band().unwrap();
+        assert_eq!((band.min_latitude, band.max_latitude), (-30.0, 90.0));
+
+        // Every spawn lands in the band, the bottom pole's right on its edge
+        for handle in 0..6 {
+            let spawn = fit_to_arena(spawn_transform(handle, 6), &physics);
+            let latitude = latitude(spawn.translation);
+            assert!((-30.01..=90.0).contains(&latitude), "spawned at {latitude}");
+        }
+    }
 }
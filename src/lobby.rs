@@ -1,26 +1,94 @@
-use bevy::prelude::*;
-use bevy_ggrs::{ggrs::DesyncDetection, prelude::*};
+use std::hash::BuildHasher;
+
+use bevy::{
+    platform::{collections::HashMap, hash::FixedHasher},
+    prelude::*,
+};
+use bevy_ggrs::{
+    ggrs::{DesyncDetection, PlayerType},
+    prelude::*,
+};
 use bevy_matchbox::prelude::*;
 
-use crate::{FPS, GameState, game, lobby_config::LobbyConfig};
+use crate::{
+    FPS, GameState,
+    disconnect::Disconnects,
+    game::{self, DeathStack, GameRng, Handicap, Handicaps, MatchId, Scores},
+    lobby_config::LobbyConfig,
+    net_messages::{GGRS_CHANNEL, MetaMessage, MetaReceived, SendMeta},
+    net_sim::{NetworkSimulation, SimulatedSocket},
+    observer::{self, ObserverConfig, ObserverSocket},
+    settings::NetworkSettings,
+};
 
 pub struct LobbyPlugin;
 
+/// How long Quick Play waits for the room to fill before going back to picking a room by hand
+const QUICK_PLAY_TIMEOUT_SECS: f32 = 60.0;
+
+/// How long to wait for everyone to agree on who's in the match once the room looks full
+const READY_TIMEOUT_SECS: f32 = 10.0;
+
+/// [`MetaMessage::Ready`] messages sent and received in the current lobby
+#[derive(Resource)]
+struct ReadyBarrier {
+    sent: Option<Vec<u128>>,
+    received: HashMap<PeerId, Vec<u128>>,
+    /// Handicap each peer picked, from their latest Ready
+    handicaps: HashMap<PeerId, Handicap>,
+    timeout: Timer,
+}
+
+impl Default for ReadyBarrier {
+    fn default() -> Self {
+        ReadyBarrier {
+            sent: None,
+            received: HashMap::new(),
+            handicaps: HashMap::new(),
+            timeout: Timer::from_seconds(READY_TIMEOUT_SECS, TimerMode::Once),
+        }
+    }
+}
+
+/// Present while waiting in a Quick Play room
+#[derive(Resource, Deref, DerefMut)]
+pub struct QuickPlayTimeout(Timer);
+
+impl Default for QuickPlayTimeout {
+    fn default() -> Self {
+        QuickPlayTimeout(Timer::from_seconds(
+            QUICK_PLAY_TIMEOUT_SECS,
+            TimerMode::Once,
+        ))
+    }
+}
+
 #[derive(Default, Clone, Copy, Component)]
 struct LobbyEntity;
 
 #[derive(Default, Clone, Copy, Component)]
 struct MainText;
 
+/// Which color each connected player will be
+#[derive(Default, Clone, Copy, Component)]
+struct ColorLegend;
+
 impl Plugin for LobbyPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Lobby), lobby_setup)
             .add_systems(OnExit(GameState::Lobby), lobby_cleanup)
-            .add_systems(Update, lobby_system.run_if(in_state(GameState::Lobby)));
+            .add_systems(
+                Update,
+                (lobby_system, update_color_legend)
+                    .run_if(in_state(GameState::Lobby))
+                    .run_if(not(resource_exists::<ObserverConfig>)),
+            );
     }
 }
 
 fn lobby_setup(mut commands: Commands) {
+    commands.insert_resource(ReadyBarrier::default());
+
     // All this is just for spawning centered text.
     commands
         .spawn((
@@ -50,10 +118,109 @@ fn lobby_setup(mut commands: Commands) {
                     TextColor(Color::BLACK),
                 ))
                 .insert(MainText);
+            parent.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: px(16),
+                    left: px(16),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: px(8),
+                    ..default()
+                },
+                ColorLegend,
+            ));
         })
         .insert(LobbyEntity);
 }
 
+/// Lists everyone connected so far in handle order, with the color they'll play as
+fn update_color_legend(
+    mut commands: Commands,
+    config: Res<LobbyConfig>,
+    socket: Option<ResMut<MatchboxSocket>>,
+    ready: Res<ReadyBarrier>,
+    legend: Single<Entity, With<ColorLegend>>,
+    mut shown: Local<Option<(Entity, Vec<(bool, Option<Handicap>)>, bool)>>,
+) {
+    // The channel is taken once the session starts, by then the list is final anyway
+    let Some(mut socket) = socket else {
+        return;
+    };
+
+    // Handles are assigned in this order, see lobby_system. Other players' handicaps show up
+    // once they're ready.
+    let rows: Vec<(bool, Option<Handicap>)> = socket
+        .players()
+        .iter()
+        .map(|player| match player {
+            PlayerType::Local => (true, Some(config.handicap)),
+            PlayerType::Remote(peer) => (false, ready.handicaps.get(peer).copied()),
+            PlayerType::Spectator(_) => (false, None),
+        })
+        .collect();
+    let locked = ready.sent.is_some();
+
+    // The legend itself is new each time we enter the lobby
+    if shown.as_ref() == Some(&(*legend, rows.clone(), locked)) {
+        return;
+    }
+
+    commands
+        .entity(*legend)
+        .despawn_children()
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(if locked {
+                    "Players (locked in)"
+                } else {
+                    "Players"
+                }),
+                TextFont {
+                    font_size: 33.,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+            ));
+
+            for (handle, (local, handicap)) in rows.iter().enumerate() {
+                let you = if *local { " (you)" } else { "" };
+                let handicap = match handicap {
+                    Some(Handicap::None) | None => String::new(),
+                    Some(handicap) => format!(", {}", handicap.name()),
+                };
+                parent.spawn((
+                    Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: px(8),
+                        ..default()
+                    },
+                    children![
+                        (
+                            Node {
+                                width: px(24),
+                                height: px(24),
+                                ..default()
+                            },
+                            BackgroundColor(game::slot_color(handle, config.players)),
+                        ),
+                        (
+                            Text::new(format!("Player {}{you}{handicap}", handle + 1)),
+                            TextFont {
+                                font_size: 33.,
+                                ..default()
+                            },
+                            TextColor(Color::BLACK),
+                        ),
+                    ],
+                ));
+            }
+        });
+
+    *shown = Some((*legend, rows, locked));
+}
+
+#[allow(clippy::too_many_arguments)]
 fn lobby_system(
     mut app_state: ResMut<NextState<GameState>>,
     config: Res<LobbyConfig>,
@@ -61,6 +228,14 @@ fn lobby_system(
     mut commands: Commands,
     mut text: Single<&mut Text, With<MainText>>,
     existing_session: Option<ResMut<Session<game::GameConfig>>>,
+    observer_socket: Option<ResMut<ObserverSocket>>,
+    network_simulation: Option<Res<NetworkSimulation>>,
+    network: Res<NetworkSettings>,
+    quick_play_timeout: Option<ResMut<QuickPlayTimeout>>,
+    mut ready: ResMut<ReadyBarrier>,
+    mut received: MessageReader<MetaReceived>,
+    mut outgoing: MessageWriter<SendMeta>,
+    time: Res<Time>,
 ) {
     // regularly call update_peers to update the list of connected peers
     let Ok(peer_changes) = socket.try_update_peers() else {
@@ -77,13 +252,62 @@ fn lobby_system(
         }
     }
 
-    let connected_peers = socket.connected_peers().count();
-    let remaining = config.players - (connected_peers + 1);
+    for received in received.read() {
+        if let MetaMessage::Ready { peers, handicap } = &received.message {
+            ready.received.insert(received.from, peers.clone());
+            ready.handicaps.insert(received.from, *handicap);
+        }
+    }
+
+    // Every peer knows every peer's id, so this is the same for everyone once they agree
+    let own_id = socket.id();
+    let connected: Vec<PeerId> = socket.connected_peers().collect();
+    let mut peer_ids: Vec<u128> = connected
+        .iter()
+        .copied()
+        .chain(own_id)
+        .map(|peer| peer.0.as_u128())
+        .collect();
+    peer_ids.sort();
+
+    let remaining = config.players.saturating_sub(connected.len() + 1);
     text.0 = format!("Waiting for {remaining} more player(s)",);
     if remaining > 0 {
+        // Someone left before we all agreed, start over
+        ready.sent = None;
+        ready.timeout.reset();
+
+        if let Some(mut timeout) = quick_play_timeout
+            && timeout.tick(time.delta()).just_finished()
+        {
+            warn!("nobody else quick playing, back to picking a room");
+            app_state.set(GameState::LobbyConfig);
+        }
         return;
     }
 
+    if ready.sent.as_ref() != Some(&peer_ids) {
+        outgoing.write(SendMeta(MetaMessage::Ready {
+            peers: peer_ids.clone(),
+            handicap: config.handicap,
+        }));
+        ready.sent = Some(peer_ids.clone());
+    }
+
+    let all_ready = connected
+        .iter()
+        .all(|peer| ready.received.get(peer) == Some(&peer_ids));
+    if !all_ready {
+        text.0 = "Waiting for everyone to be ready".to_string();
+        if ready.timeout.tick(time.delta()).just_finished() {
+            warn!("not everyone agreed on who's playing, back to picking a room");
+            app_state.set(GameState::LobbyConfig);
+        }
+        return;
+    }
+
+    commands.remove_resource::<QuickPlayTimeout>();
+
     info!("All peers have joined, going in-game");
     if existing_session.is_some() {
         // transition to in-game state
@@ -97,26 +321,52 @@ fn lobby_system(
     // create a GGRS P2P session
     let mut sess_build = SessionBuilder::<game::GameConfig>::new()
         .with_num_players(config.players)
-        .with_max_prediction_window(12)
+        .with_max_prediction_window(network.max_prediction_frames)
         .with_input_delay(2)
         .with_desync_detection_mode(DesyncDetection::On {
             interval: FPS as u32,
         });
 
-    for (i, player) in players.into_iter().enumerate() {
+    for (i, player) in players.iter().enumerate() {
         sess_build = sess_build
-            .add_player(player, i)
+            .add_player(player.clone(), i)
             .expect("failed to add player");
     }
 
-    let channel = socket.take_channel(0).unwrap();
+    let channel = SimulatedSocket::new(
+        socket.take_channel(GGRS_CHANNEL).unwrap(),
+        network_simulation.as_deref().copied(),
+    );
+
+    // Everyone agreed on these ids, and they're different each match
+    let seed = FixedHasher.hash_one(peer_ids);
+
+    // Everyone sent theirs along with the ids we just agreed on, so this is the same for everyone
+    let handicaps = Handicaps(
+        players
+            .iter()
+            .map(|player| match player {
+                PlayerType::Remote(peer) => ready.handicaps.get(peer).copied().unwrap_or_default(),
+                _ => config.handicap,
+            })
+            .collect(),
+    );
 
     // start the GGRS session
-    let sess = sess_build
-        .start_p2p_session(channel)
-        .expect("failed to start session");
+    let sess = observer::start_p2p_session(
+        sess_build,
+        &players,
+        channel,
+        seed,
+        &handicaps.0,
+        &mut commands,
+        observer_socket,
+    );
 
-    commands.insert_resource(Session::P2P(sess));
+    commands.insert_resource(sess);
+    commands.insert_resource(GameRng::new(seed));
+    commands.insert_resource(MatchId(seed));
+    commands.insert_resource(handicaps);
 
     // transition to in-game state
     app_state.set(GameState::Playing);
@@ -127,3 +377,33 @@ fn lobby_cleanup(mut commands: Commands, entities: Query<Entity, With<LobbyEntit
         commands.entity(entity).despawn();
     }
 }
+
+/// Tears down everything left over from a match, so the next one starts clean. Every way out of
+/// a match should end up here, queue it with `commands.queue(teardown_match)`.
+pub fn teardown_match(world: &mut World) {
+    if let Some(mut socket) = world.remove_resource::<MatchboxSocket>() {
+        socket.close();
+    }
+
+    if let Some(mut observer_socket) = world.remove_resource::<ObserverSocket>() {
+        observer_socket.close();
+    }
+
+    world.remove_resource::<Session<game::GameConfig>>();
+    world.remove_resource::<MatchId>();
+    world.insert_resource(Handicaps::default());
+    world.insert_resource(Disconnects::default());
+
+    // Most of these go with DespawnOnExit already, but nothing rolled back may outlive the session
+    let rollback_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<Rollback>>()
+        .iter(world)
+        .collect();
+    for entity in rollback_entities {
+        world.despawn(entity);
+    }
+
+    world.remove_resource::<QuickPlayTimeout>();
+    world.resource_mut::<Scores>().clear();
+    world.resource_mut::<DeathStack>().clear();
+}
@@ -1,17 +1,49 @@
-use bevy::prelude::*;
-use bevy_ggrs::Session;
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
 use bevy_matchbox::{
     MatchboxSocket,
     matchbox_socket::{RtcIceServerConfig, WebRtcSocket},
 };
 
-use crate::{GameState, game};
+use uuid::Uuid;
+
+use crate::{
+    GameState,
+    game::{Handicap, MatchId},
+    lobby::{QuickPlayTimeout, teardown_match},
+    net_messages,
+    physics_config::{ArenaBand, ArenaEdge, CasualRules, PhysicsConfig},
+    room_browser::{RoomInfo, Rooms},
+    settings::ResetSettings,
+    start_synctest_session,
+};
 
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct LobbyConfig {
     pub players: usize,
     pub server: String,
     pub room: String,
+    /// Private room code, empty when joining a public room
+    pub code: String,
+    /// Our own handicap, sent to everyone else in the lobby
+    pub handicap: Handicap,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        LobbyConfig {
+            players: 0,
+            server: matchbox_server(),
+            room: String::new(),
+            code: String::new(),
+            handicap: Handicap::default(),
+        }
+    }
 }
 
 pub struct LobbyConfigPlugin;
@@ -19,18 +51,66 @@ pub struct LobbyConfigPlugin;
 #[derive(Component)]
 struct ConfigLobbyEntity;
 
+/// Container the room browser's buttons are spawned into
+#[derive(Component)]
+struct RoomList;
+
+/// Shows which room will be joined
+#[derive(Component)]
+struct SelectedRoomText;
+
+/// Shows whether the match will be casual, see [`PhysicsConfig::casual`]
+#[derive(Component)]
+struct ModeText;
+
+/// Shows the handicap we'll play with
+#[derive(Component)]
+struct HandicapText;
+
 #[derive(Component)]
 enum ButtonType {
-    TwoPlayers,
-    ThreePlayers,
-    FourPlayers,
-    FivePlayers,
-    SixPlayers,
+    Players(usize),
+    Room(RoomInfo),
+    CreateRoom,
+    CreatePrivateRoom,
+    QuickPlay,
     Join,
+    Practice,
+    Casual,
+    Comeback,
+    Arena,
+    Handicap,
+    ResetSettings,
 }
 
 const MIN_PLAYERS: usize = 2;
-const MAX_PLAYERS: usize = 6;
+/// Every peer exchanges inputs with every other peer each frame, past this the full mesh gets too
+/// chatty for GGRS to keep up over typical home connections
+pub const MAX_PLAYERS: usize = 12;
+/// Play areas the Arena button cycles through, the whole sphere first
+const ARENAS: [Option<ArenaBand>; 4] = [
+    None,
+    Some(ArenaBand {
+        min_latitude: -45.0,
+        max_latitude: 45.0,
+        edge: ArenaEdge::Kill,
+    }),
+    Some(ArenaBand {
+        min_latitude: -45.0,
+        max_latitude: 45.0,
+        edge: ArenaEdge::Bounce,
+    }),
+    Some(ArenaBand {
+        min_latitude: 0.0,
+        max_latitude: 90.0,
+        edge: ArenaEdge::Kill,
+    }),
+];
+/// Public room used when no room was picked or created
+const DEFAULT_ROOM: &str = "bevy_ggrs";
+const ROOM_CODE_LEN: usize = 6;
+/// Characters used in room codes, without the easily confused 0/O and 1/I
+const ROOM_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
 
 impl Plugin for LobbyConfigPlugin {
     fn build(&self, app: &mut App) {
@@ -39,7 +119,15 @@ impl Plugin for LobbyConfigPlugin {
             .add_systems(OnExit(GameState::LobbyConfig), lobby_config_cleanup)
             .add_systems(
                 Update,
-                lobby_config_system.run_if(in_state(GameState::LobbyConfig)),
+                (
+                    lobby_config_system,
+                    room_code_input,
+                    update_room_list,
+                    update_selected_room_text,
+                    update_mode_text,
+                    update_handicap_text,
+                )
+                    .run_if(in_state(GameState::LobbyConfig)),
             );
     }
 }
@@ -47,18 +135,17 @@ impl Plugin for LobbyConfigPlugin {
 fn lobby_config_setup(
     mut commands: Commands,
     mut lobby_config: ResMut<LobbyConfig>,
+    physics: Res<PhysicsConfig>,
     _asset_server: Res<AssetServer>,
-    old_socket: Option<ResMut<MatchboxSocket>>,
 ) {
-    *lobby_config = LobbyConfig::default();
+    // Keep the handicap, it's a choice about the player rather than the room
+    *lobby_config = LobbyConfig {
+        handicap: lobby_config.handicap,
+        ..default()
+    };
 
     // Reset networking stuff when entering lobby_config
-    if let Some(mut old_socket) = old_socket {
-        old_socket.close();
-        commands.remove_resource::<MatchboxSocket>();
-    }
-
-    commands.remove_resource::<Session<game::GameConfig>>();
+    commands.queue(teardown_match);
 
     // All this is just for spawning centered text.
     commands
@@ -94,13 +181,52 @@ fn lobby_config_setup(
                     justify_content: JustifyContent::Center,
                     ..Default::default()
                 },
-                Text::new("# of Players"),
+                Text::new(format!("# of Players ({MIN_PLAYERS}-{MAX_PLAYERS})")),
                 TextFont {
                     font_size: 96.,
                     ..default()
                 },
                 TextColor(Color::BLACK),
             ));
+            parent
+                .spawn(Node {
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for players in MIN_PLAYERS..=MAX_PLAYERS {
+                        parent.spawn(button(players.to_string(), ButtonType::Players(players)));
+                    }
+                });
+
+            parent.spawn((
+                Node {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                Text::new("Rooms"),
+                TextFont {
+                    font_size: 48.,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+            ));
+            parent.spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Row,
+                    flex_wrap: FlexWrap::Wrap,
+                    ..default()
+                },
+                RoomList,
+            ));
             parent.spawn((
                 Node {
                     width: Val::Percent(100.0),
@@ -110,15 +236,72 @@ fn lobby_config_setup(
                     ..default()
                 },
                 children![
-                    button("2", ButtonType::TwoPlayers),
-                    button("3", ButtonType::ThreePlayers),
-                    button("4", ButtonType::FourPlayers),
-                    button("5", ButtonType::FivePlayers),
-                    button("6", ButtonType::SixPlayers),
+                    button("Create Room", ButtonType::CreateRoom),
+                    button("Create Private", ButtonType::CreatePrivateRoom),
                 ],
             ));
+            parent.spawn((
+                Node {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                Text::new(selected_room_text(&LobbyConfig::default())),
+                TextFont {
+                    font_size: 33.,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+                SelectedRoomText,
+            ));
+            parent.spawn((
+                Node {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                Text::new(mode_text(&physics)),
+                TextFont {
+                    font_size: 33.,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+                ModeText,
+            ));
+            parent.spawn((
+                Node {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                Text::new(handicap_text(lobby_config.handicap)),
+                TextFont {
+                    font_size: 33.,
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+                HandicapText,
+            ));
 
-            parent.spawn(button("Join!", ButtonType::Join));
+            parent.spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Row,
+                    ..default()
+                },
+                children![
+                    button("Quick Play", ButtonType::QuickPlay),
+                    button("Join!", ButtonType::Join),
+                    button("Practice", ButtonType::Practice),
+                    button("Casual", ButtonType::Casual),
+                    button("Comeback", ButtonType::Comeback),
+                    button("Arena", ButtonType::Arena),
+                    button("Handicap", ButtonType::Handicap),
+                    button("Reset Settings", ButtonType::ResetSettings),
+                ],
+            ));
         })
         .insert(ConfigLobbyEntity);
 }
@@ -127,6 +310,8 @@ fn lobby_config_system(
     mut commands: Commands,
     mut app_state: ResMut<NextState<GameState>>,
     mut lobby_config: ResMut<LobbyConfig>,
+    mut physics: ResMut<PhysicsConfig>,
+    mut reset_settings: MessageWriter<ResetSettings>,
     mut interaction_query: Query<
         (Entity, &Interaction, &mut Button, &ButtonType),
         Changed<Interaction>,
@@ -136,60 +321,95 @@ fn lobby_config_system(
         match *interaction {
             Interaction::Pressed => {
                 match button_type {
-                    ButtonType::TwoPlayers => {
-                        lobby_config.players = 2;
+                    ButtonType::Players(players) => {
+                        lobby_config.players = *players;
                     }
-                    ButtonType::ThreePlayers => {
-                        lobby_config.players = 3;
+                    ButtonType::Room(info) => {
+                        lobby_config.room = info.room.clone();
+                        lobby_config.code.clear();
+                        lobby_config.players = info.max_players;
                     }
-                    ButtonType::FourPlayers => {
-                        lobby_config.players = 4;
+                    ButtonType::CreateRoom => {
+                        lobby_config.room =
+                            format!("room-{}", &Uuid::new_v4().simple().to_string()[..6]);
+                        lobby_config.code.clear();
                     }
-                    ButtonType::FivePlayers => {
-                        lobby_config.players = 5;
+                    ButtonType::CreatePrivateRoom => {
+                        lobby_config.code = generate_room_code();
                     }
-                    ButtonType::SixPlayers => {
-                        lobby_config.players = 6;
+                    ButtonType::ResetSettings => {
+                        reset_settings.write(ResetSettings);
                     }
-                    ButtonType::Join => {
-                        // TODO: actually input server/room
-                        #[cfg(not(debug_assertions))]
-                        {
-                            lobby_config.server = "wss://gc-matchbox.igamble.dev".into();
+                    ButtonType::Casual => {
+                        // Part of the room's fingerprint, so casual players only meet each other
+                        physics.casual = match physics.casual {
+                            Some(_) => None,
+                            None => Some(CasualRules::default()),
+                        };
+                    }
+                    ButtonType::Comeback => {
+                        // Also part of the fingerprint
+                        physics.comeback_bonus = if physics.comeback_bonus > 0 { 0 } else { 1 };
+                    }
+                    ButtonType::Arena => {
+                        // Also part of the fingerprint. A hand-edited band goes back to the
+                        // whole sphere.
+                        let next = ARENAS
+                            .iter()
+                            .position(|arena| *arena == physics.arena_band)
+                            .map_or(0, |i| (i + 1) % ARENAS.len());
+                        physics.arena_band = ARENAS[next];
+                    }
+                    ButtonType::Handicap => {
+                        let next = Handicap::ALL
+                            .iter()
+                            .position(|handicap| *handicap == lobby_config.handicap)
+                            .map_or(0, |i| (i + 1) % Handicap::ALL.len());
+                        lobby_config.handicap = Handicap::ALL[next];
+                    }
+                    ButtonType::Practice => {
+                        // Just us, no server or other players needed
+                        match start_synctest_session(1, 0) {
+                            Ok(session) => {
+                                commands.insert_resource(session);
+                                commands.insert_resource(MatchId(Uuid::new_v4().as_u64_pair().0));
+                                app_state.set(GameState::Playing);
+                                return;
+                            }
+                            Err(err) => error!("failed to start practice: {err}"),
+                        }
+                    }
+                    ButtonType::QuickPlay => {
+                        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&lobby_config.players) {
+                            warn!("pick a number of players before quick play");
+                            continue;
+                        }
+
+                        // Everyone quick playing with the same player count shares one room name,
+                        // the server's `next` grouping splits them into matches as they arrive,
+                        // so two people going for the last spot can't both get it
+                        lobby_config.room = quick_play_room(lobby_config.players);
+                        lobby_config.code.clear();
+
+                        if connect_to_room(&mut commands, &lobby_config, &physics) {
+                            commands.insert_resource(QuickPlayTimeout::default());
+                            app_state.set(GameState::Lobby);
+                            return;
                         }
-                        #[cfg(debug_assertions)]
-                        {
-                            lobby_config.server = "ws://localhost:3536".into();
+                    }
+                    ButtonType::Join => {
+                        if !lobby_config.code.is_empty() {
+                            if !is_valid_room_code(&lobby_config.code) {
+                                warn!("invalid room code: {:?}", lobby_config.code);
+                                continue;
+                            }
+
+                            lobby_config.room = room_for_code(&lobby_config.code);
+                        } else if lobby_config.room.is_empty() {
+                            lobby_config.room = DEFAULT_ROOM.into();
                         }
 
-                        lobby_config.room = "bevy_ggrs".into();
-                        if (MIN_PLAYERS..=MAX_PLAYERS).contains(&lobby_config.players)
-                            && !lobby_config.server.is_empty()
-                            && !lobby_config.room.is_empty()
-                        {
-                            // connect and transition to lobby state
-                            let room_url = format!(
-                                "{}/{}?next={}",
-                                lobby_config.server, lobby_config.room, lobby_config.players
-                            );
-                            info!("connecting to matchbox server: {room_url:?}");
-
-                            commands.insert_resource(MatchboxSocket::from(
-                                WebRtcSocket::builder(room_url)
-                                    .add_unreliable_channel()
-                                    .ice_server(RtcIceServerConfig {
-                                        urls: vec![
-                                            "stun:stun.l.google.com:19302".to_string(),
-                                            "stun:stun1.l.google.com:19302".to_string(),
-                                            "turn:gc-server.igamble.dev:3478".to_string(),
-                                            "turn:gc-server.igamble.dev:3478?transport=tcp".to_string(),
-                                        ],
-                                        // TODO: real turn auth???
-                                        username: Some("username".into()),
-                                        credential: Some("password".into()),
-                                    })
-                                    .build(),
-                            ));
+                        if connect_to_room(&mut commands, &lobby_config, &physics) {
                             app_state.set(GameState::Lobby);
                             return;
                         }
@@ -202,13 +422,220 @@ fn lobby_config_system(
     }
 }
 
+/// Connects to the configured room, only matching with peers simulating with the same physics.
+/// Returns false if the config isn't complete enough to join.
+fn connect_to_room(
+    commands: &mut Commands,
+    lobby_config: &LobbyConfig,
+    physics: &PhysicsConfig,
+) -> bool {
+    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&lobby_config.players)
+        || lobby_config.server.is_empty()
+        || lobby_config.room.is_empty()
+    {
+        return false;
+    }
+
+    let room_url = format!(
+        "{}/{}-{:08x}?next={}",
+        lobby_config.server,
+        lobby_config.room,
+        physics.fingerprint(),
+        lobby_config.players
+    );
+    info!("connecting to matchbox server: {room_url:?}");
+
+    commands.insert_resource(MatchboxSocket::from(
+        net_messages::with_channels(WebRtcSocket::builder(room_url))
+            .ice_server(RtcIceServerConfig {
+                urls: vec![
+                    "stun:stun.l.google.com:19302".to_string(),
+                    "stun:stun1.l.google.com:19302".to_string(),
+                    "turn:gc-server.igamble.dev:3478".to_string(),
+                    "turn:gc-server.igamble.dev:3478?transport=tcp".to_string(),
+                ],
+                // TODO: real turn auth???
+                username: Some("username".into()),
+                credential: Some("password".into()),
+            })
+            .build(),
+    ));
+
+    true
+}
+
+/// Rebuilds the room buttons whenever the list of known rooms changes
+fn update_room_list(
+    mut commands: Commands,
+    rooms: Res<Rooms>,
+    room_list: Single<Entity, With<RoomList>>,
+) {
+    if !rooms.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(*room_list)
+        .despawn_children()
+        .with_children(|parent| {
+            if rooms.is_empty() {
+                parent.spawn((
+                    Text::new("No open rooms, create one!"),
+                    TextFont {
+                        font_size: 33.,
+                        ..default()
+                    },
+                    TextColor(Color::BLACK),
+                ));
+            }
+
+            for info in rooms.iter() {
+                parent.spawn(button(
+                    format!("{} ({}/{})", info.room, info.players, info.max_players),
+                    ButtonType::Room(info.clone()),
+                ));
+            }
+        });
+}
+
+fn update_selected_room_text(
+    lobby_config: Res<LobbyConfig>,
+    mut text: Single<&mut Text, With<SelectedRoomText>>,
+) {
+    if !lobby_config.is_changed() {
+        return;
+    }
+
+    text.0 = selected_room_text(&lobby_config);
+}
+
+fn update_mode_text(physics: Res<PhysicsConfig>, mut text: Single<&mut Text, With<ModeText>>) {
+    if !physics.is_changed() {
+        return;
+    }
+
+    text.0 = mode_text(&physics);
+}
+
+fn mode_text(physics: &PhysicsConfig) -> String {
+    let mode = match physics.casual {
+        Some(_) => "Mode: Casual (respawns, a point per kill)",
+        None => "Mode: Classic (last one standing)",
+    };
+    let mut text = mode.to_string();
+    if physics.comeback_bonus > 0 {
+        text.push_str(", comeback bonus");
+    }
+    if let Some(band) = physics.arena_band() {
+        text.push_str(&format!(
+            ", arena {}° to {}° ({:?} at the edge)",
+            band.min_latitude, band.max_latitude, band.edge
+        ));
+    }
+    text
+}
+
+fn update_handicap_text(
+    lobby_config: Res<LobbyConfig>,
+    mut text: Single<&mut Text, With<HandicapText>>,
+) {
+    if !lobby_config.is_changed() {
+        return;
+    }
+
+    text.0 = handicap_text(lobby_config.handicap);
+}
+
+fn handicap_text(handicap: Handicap) -> String {
+    format!(
+        "Handicap: {} (everyone sees it in the lobby)",
+        handicap.name()
+    )
+}
+
+fn selected_room_text(lobby_config: &LobbyConfig) -> String {
+    if !lobby_config.code.is_empty() {
+        let blanks = "_".repeat(ROOM_CODE_LEN.saturating_sub(lobby_config.code.len()));
+        return format!("Private room code: {}{blanks}", lobby_config.code);
+    }
+
+    let room = if lobby_config.room.is_empty() {
+        DEFAULT_ROOM
+    } else {
+        &lobby_config.room
+    };
+    format!("Room: {room} (type a code to join a private room)")
+}
+
+/// Lets players type a private room code while on the config screen
+fn room_code_input(
+    mut keyboard_input: MessageReader<KeyboardInput>,
+    mut lobby_config: ResMut<LobbyConfig>,
+) {
+    for input in keyboard_input.read() {
+        if input.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match &input.logical_key {
+            Key::Backspace => {
+                lobby_config.code.pop();
+            }
+            Key::Character(chars) => {
+                for c in chars.chars().map(|c| c.to_ascii_uppercase()) {
+                    if lobby_config.code.len() < ROOM_CODE_LEN
+                        && c.is_ascii()
+                        && ROOM_CODE_ALPHABET.contains(&(c as u8))
+                    {
+                        lobby_config.code.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn generate_room_code() -> String {
+    Uuid::new_v4().as_bytes()[..ROOM_CODE_LEN]
+        .iter()
+        .map(|byte| ROOM_CODE_ALPHABET[*byte as usize % ROOM_CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+fn is_valid_room_code(code: &str) -> bool {
+    code.len() == ROOM_CODE_LEN && code.bytes().all(|b| ROOM_CODE_ALPHABET.contains(&b))
+}
+
+/// Room everyone quick playing with this many players goes to
+fn quick_play_room(players: usize) -> String {
+    format!("quick-{players}p")
+}
+
+/// Matchbox room a private code maps to
+fn room_for_code(code: &str) -> String {
+    format!("private-{code}")
+}
+
+fn matchbox_server() -> String {
+    // TODO: actually input server
+    #[cfg(not(debug_assertions))]
+    {
+        "wss://gc-matchbox.igamble.dev".into()
+    }
+    #[cfg(debug_assertions)]
+    {
+        "ws://localhost:3536".into()
+    }
+}
+
 fn lobby_config_cleanup(mut commands: Commands, entities: Query<Entity, With<ConfigLobbyEntity>>) {
     for entity in entities {
         commands.entity(entity).despawn();
     }
 }
 
-fn button(text: impl Into<String>, extra_bundle: impl Bundle) -> impl Bundle {
+pub fn button(text: impl Into<String>, extra_bundle: impl Bundle) -> impl Bundle {
     (
         Button,
         Node {
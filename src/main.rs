@@ -2,14 +2,49 @@
 
 #![feature(iter_intersperse)]
 
+mod cli;
+#[cfg(debug_assertions)]
+mod debug_inspector;
+mod disconnect;
 pub mod game;
 mod lobby;
 mod lobby_config;
+mod music;
+mod net_messages;
+mod net_sim;
+mod observer;
+mod pause_menu;
+mod physics_config;
+mod room_browser;
+mod settings;
 
-use bevy::{prelude::*, window::WindowResolution};
-use bevy_ggrs::{Session, ggrs::GgrsEvent};
+use std::time::Duration;
 
-use crate::{game::GamePlugin, lobby::LobbyPlugin, lobby_config::LobbyConfigPlugin};
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::*,
+    render::{RenderPlugin, settings::WgpuSettings},
+    window::{ExitCondition, WindowResolution},
+    winit::WinitPlugin,
+};
+use bevy_ggrs::{
+    Session,
+    ggrs::{GgrsEvent, PlayerType, SessionBuilder},
+};
+
+use crate::{
+    cli::Cli,
+    disconnect::{DisconnectPlugin, Disconnects},
+    game::GamePlugin,
+    lobby::LobbyPlugin,
+    lobby_config::{LobbyConfig, LobbyConfigPlugin},
+    music::MusicPlugin,
+    net_messages::NetMessagesPlugin,
+    observer::{ObserverConfig, ObserverPlugin},
+    pause_menu::PauseMenuPlugin,
+    room_browser::RoomBrowserPlugin,
+    settings::SettingsPlugin,
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States)]
 pub enum GameState {
@@ -31,8 +66,21 @@ const FPS: usize = 30;
 struct NetworkStatsTimer(Timer);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let cli = Cli::parse()?;
+    let mut app = App::new();
+
+    if let Some(simulation) = cli.network_simulation() {
+        if !cfg!(debug_assertions) {
+            return Err("network simulation is only available in debug builds".into());
+        }
+
+        app.insert_resource(simulation);
+    }
+
+    if cli.headless || cli.observe.is_some() {
+        add_headless_plugins(&mut app);
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 resolution: WindowResolution::new(640, 640),
                 title: "Galaxy Cats".to_owned(),
@@ -44,8 +92,50 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }),
             ..default()
         }))
-        .init_state::<GameState>()
-        .add_plugins((LobbyConfigPlugin, LobbyPlugin, GamePlugin))
+        // Nobody's listening to a headless instance
+        .add_plugins(MusicPlugin);
+    }
+
+    if let Some(room) = cli.observe {
+        let players = cli.players.ok_or("--observe needs --players")?;
+        let mut lobby_config = LobbyConfig {
+            players,
+            room,
+            ..default()
+        };
+        if let Some(server) = cli.server {
+            lobby_config.server = server;
+        }
+
+        app.insert_resource(lobby_config)
+            .insert_resource(ObserverConfig {
+                http_addr: cli.http.unwrap_or_else(|| "127.0.0.1:8080".into()),
+                delay_secs: cli.delay_secs.unwrap_or(0.0).max(0.0),
+            })
+            .insert_state(GameState::Lobby);
+    } else if let Some(players) = cli.synctest {
+        app.insert_resource(start_synctest_session(players, 7)?)
+            .insert_resource(game::MatchId(uuid::Uuid::new_v4().as_u64_pair().0))
+            .insert_state(GameState::Playing);
+    } else {
+        app.init_state::<GameState>();
+    }
+
+    app.add_plugins(SettingsPlugin).add_plugins((
+        LobbyConfigPlugin,
+        RoomBrowserPlugin,
+        LobbyPlugin,
+        NetMessagesPlugin,
+        ObserverPlugin,
+        PauseMenuPlugin,
+        DisconnectPlugin,
+        GamePlugin,
+    ));
+
+    #[cfg(debug_assertions)]
+    app.add_plugins(debug_inspector::DebugInspectorPlugin);
+
+    app
         // print some network stats - not part of the rollback schedule as it does not need to be rolled back
         .insert_resource(NetworkStatsTimer(Timer::from_seconds(
             2.0,
@@ -58,14 +148,65 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn print_events_system(mut session: Option<ResMut<Session<game::GameConfig>>>) {
+/// Runs the simulation without a window or a renderer
+fn add_headless_plugins(app: &mut App) {
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+    )
+    .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+        1.0 / FPS as f64,
+    )));
+}
+
+/// Local session where every player is on this machine and each frame is rolled back and
+/// re-simulated `check_distance` frames, any nondeterminism shows up as a checksum mismatch on the
+/// frame it happened. 0 skips the re-simulation, for just playing offline.
+fn start_synctest_session(
+    num_players: usize,
+    check_distance: usize,
+) -> Result<Session<game::GameConfig>, Box<dyn std::error::Error>> {
+    let mut sess_build = SessionBuilder::<game::GameConfig>::new()
+        .with_num_players(num_players)
+        .with_check_distance(check_distance);
+
+    for handle in 0..num_players {
+        sess_build = sess_build.add_player(PlayerType::Local, handle)?;
+    }
+
+    Ok(Session::SyncTest(sess_build.start_synctest_session()?))
+}
+
+fn print_events_system(
+    mut session: Option<ResMut<Session<game::GameConfig>>>,
+    mut disconnects: ResMut<Disconnects>,
+    context: game::MatchContext,
+) {
+    let _span = context.span().entered();
+
     match session.as_deref_mut() {
         Some(Session::P2P(s)) => {
-            for event in s.events() {
+            let events: Vec<_> = s.events().collect();
+            for event in events {
                 match event {
-                    GgrsEvent::Disconnected { .. } | GgrsEvent::NetworkInterrupted { .. } => {
-                        log::warn!("GGRS event: {event:?}")
+                    GgrsEvent::Disconnected { addr } => {
+                        log::warn!("GGRS event: {event:?}");
+                        disconnects.peer_disconnected(s, addr);
                     }
+                    GgrsEvent::NetworkInterrupted { .. } => log::warn!("GGRS event: {event:?}"),
                     GgrsEvent::DesyncDetected { .. } => log::error!("GGRS event: {event:?}"),
                     _ => log::info!("GGRS event: {event:?}"),
                 }
@@ -81,7 +222,10 @@ fn print_network_stats_system(
     time: Res<Time>,
     mut timer: ResMut<NetworkStatsTimer>,
     p2p_session: Option<Res<Session<game::GameConfig>>>,
+    context: game::MatchContext,
 ) {
+    let _span = context.span().entered();
+
     // print only when timer runs out
     if timer.0.tick(time.delta()).just_finished()
         && let Some(sess) = p2p_session
@@ -95,7 +239,9 @@ fn print_network_stats_system(
                     }
                 }
             }
-            _ => panic!("This examples focuses on p2p."),
+            _ => {
+                // Only P2P sessions have per-player stats, e.g. observers run a spectator session
+            }
         }
     }
 }
@@ -0,0 +1,97 @@
+//! Background music. One track loops in the menus and another during matches, picked in
+//! [`AudioSettings`]. None of it is simulated, so rollbacks never touch it.
+
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    game::{DeathLog, NewlyConfirmed},
+    settings::{AudioSettings, SoundKind},
+};
+
+/// Every track, by index. To add one, drop it in `assets/music` and list it here.
+pub const MUSIC_TRACKS: &[(&str, &str)] =
+    &[("Drift", "music/menu.wav"), ("Orbit", "music/match.wav")];
+
+/// How much quieter music gets while a death is being shown
+const DUCK_VOLUME: f32 = 0.35;
+/// How long music stays ducked after a death
+const DUCK_SECS: f32 = 1.0;
+
+/// Which of [`MUSIC_TRACKS`] a music entity is playing
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+struct MusicTrack(usize);
+
+/// Turns music down for a moment so deaths stand out
+#[derive(Resource)]
+pub struct MusicDuck(Timer);
+
+impl Default for MusicDuck {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(DUCK_SECS, TimerMode::Once);
+        timer.finish();
+        MusicDuck(timer)
+    }
+}
+
+impl MusicDuck {
+    /// Multiplier on the music volume, easing back to 1 as the duck wears off
+    pub fn factor(&self) -> f32 {
+        DUCK_VOLUME + (1.0 - DUCK_VOLUME) * self.0.fraction()
+    }
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicDuck>()
+            .add_systems(Update, (play_music, duck_music));
+    }
+}
+
+/// Keeps the track for the current screen playing, switching when the screen or setting changes
+fn play_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<AudioSettings>,
+    app_state: Res<State<GameState>>,
+    playing: Query<(Entity, &MusicTrack)>,
+) {
+    let track = if *app_state.get() == GameState::Playing {
+        settings.match_track
+    } else {
+        settings.menu_track
+    } % MUSIC_TRACKS.len();
+
+    if playing.iter().any(|(_, playing)| playing.0 == track) {
+        return;
+    }
+
+    for (entity, _) in &playing {
+        commands.entity(entity).despawn();
+    }
+
+    let (name, path) = MUSIC_TRACKS[track];
+    info!("playing {name}");
+    commands.spawn((
+        AudioPlayer::new(asset_server.load(path)),
+        // Start at the right volume, apply_audio_volume keeps it there
+        PlaybackSettings::LOOP.with_volume(settings.volume(SoundKind::Music)),
+        SoundKind::Music,
+        MusicTrack(track),
+    ));
+}
+
+/// Ducks the music for each death, once it's confirmed so it only happens once per death
+fn duck_music(
+    mut duck: ResMut<MusicDuck>,
+    mut deaths: NewlyConfirmed<DeathLog>,
+    time: Res<Time<Real>>,
+) {
+    duck.0.tick(time.delta());
+
+    if !deaths.read().is_empty() {
+        duck.0.reset();
+    }
+}
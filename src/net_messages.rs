@@ -0,0 +1,155 @@
+//! Out-of-band messages between peers, for everything that isn't GGRS.
+//!
+//! Every socket is built with [`with_channels`], so channel numbers mean the same thing
+//! everywhere: [`GGRS_CHANNEL`] is unreliable and belongs to GGRS once a session starts, and
+//! [`META_CHANNEL`] is reliable and carries [`MetaMessage`]s for as long as the socket is open.
+//! Metadata never goes near the GGRS channel, so it can't hold up or reorder rollback traffic.
+//!
+//! On the game's [`MatchboxSocket`], features don't touch the channel themselves. They write
+//! [`SendMeta`] and read [`MetaReceived`], and [`NetMessagesPlugin`] does the rest.
+
+use bevy::prelude::*;
+use bevy_matchbox::{MatchboxSocket, matchbox_socket::WebRtcSocketBuilder, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::game::Handicap;
+
+/// Unreliable channel for GGRS, taken when the session starts
+pub const GGRS_CHANNEL: usize = 0;
+/// Reliable channel for everything else
+pub const META_CHANNEL: usize = 1;
+
+/// Sent with every message. Bump it when a message changes in a way older clients would
+/// misread. New variants and fields don't need a bump, older clients skip what they don't know.
+const PROTOCOL_VERSION: u16 = 1;
+
+/// Everything peers tell each other outside of GGRS
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MetaMessage {
+    /// A line of chat
+    Chat(String),
+    /// A quick reaction, by index
+    Emote(u8),
+    /// What the sender wants to be called
+    PlayerName(String),
+    /// Sent to every other peer once the room looks full to us, with every peer's id including
+    /// the sender's, sorted. Peers don't see each other connect at the same moment, so nobody
+    /// starts the session until everyone has said they see the exact same set of players.
+    /// Also carries the [`Handicap`] the sender picked, fixed from then on.
+    Ready {
+        peers: Vec<u128>,
+        #[serde(default)]
+        handicap: Handicap,
+    },
+    /// A vote in whatever is being voted on, by option index
+    VoteCast(u8),
+    /// Sent by an observer to everyone in the observer room, so the host adds it as a spectator
+    /// and not the other players waiting there
+    ObserverJoin,
+    /// Sent by the host to observers once they've been added to the session
+    ObserverHello {
+        num_players: usize,
+        /// Seed for [`GameRng`](crate::game::GameRng), so observers draw the same numbers as
+        /// players
+        seed: u64,
+        /// Every player's [`Handicap`] by handle, so observers move them the same way
+        #[serde(default)]
+        handicaps: Vec<Handicap>,
+    },
+}
+
+/// What actually goes over the wire. The message is parsed separately from the header, so one
+/// from a newer client that we don't understand can be skipped instead of looking corrupt.
+#[derive(Serialize, Deserialize)]
+struct Envelope<M> {
+    version: u16,
+    message: M,
+}
+
+/// Broadcast to every connected peer on the game socket
+#[derive(Message, Clone, Debug)]
+pub struct SendMeta(pub MetaMessage);
+
+/// A message that arrived on the game socket
+#[derive(Message, Clone, Debug)]
+pub struct MetaReceived {
+    pub from: PeerId,
+    pub message: MetaMessage,
+}
+
+pub struct NetMessagesPlugin;
+
+impl Plugin for NetMessagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<SendMeta>()
+            .add_message::<MetaReceived>()
+            .add_systems(
+                PreUpdate,
+                receive_meta.run_if(resource_exists::<MatchboxSocket>),
+            )
+            .add_systems(
+                PostUpdate,
+                send_meta.run_if(resource_exists::<MatchboxSocket>),
+            );
+    }
+}
+
+/// Adds the channels in the order the constants above expect
+pub fn with_channels(builder: WebRtcSocketBuilder) -> WebRtcSocketBuilder {
+    builder.add_unreliable_channel().add_reliable_channel()
+}
+
+/// Sends `message` to `peer` on the metadata channel
+pub fn send(socket: &mut MatchboxSocket, peer: PeerId, message: &MetaMessage) {
+    let packet = serde_json::to_vec(&Envelope {
+        version: PROTOCOL_VERSION,
+        message,
+    })
+    .expect("meta messages are always serializable")
+    .into_boxed_slice();
+    socket.channel_mut(META_CHANNEL).send(packet, peer);
+}
+
+/// Everything that's arrived on the metadata channel since last time
+pub fn receive(socket: &mut MatchboxSocket) -> Vec<(PeerId, MetaMessage)> {
+    socket
+        .channel_mut(META_CHANNEL)
+        .receive()
+        .into_iter()
+        .filter_map(|(peer, packet)| {
+            let envelope = match serde_json::from_slice::<Envelope<serde_json::Value>>(&packet) {
+                Ok(envelope) => envelope,
+                Err(err) => {
+                    warn!("bad message from {peer}: {err}");
+                    return None;
+                }
+            };
+
+            match serde_json::from_value(envelope.message) {
+                Ok(message) => Some((peer, message)),
+                Err(err) => {
+                    debug!(
+                        "skipping message from {peer} (protocol {}, ours is {PROTOCOL_VERSION}): {err}",
+                        envelope.version
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn receive_meta(mut socket: ResMut<MatchboxSocket>, mut received: MessageWriter<MetaReceived>) {
+    for (from, message) in receive(&mut socket) {
+        received.write(MetaReceived { from, message });
+    }
+}
+
+fn send_meta(mut socket: ResMut<MatchboxSocket>, mut outgoing: MessageReader<SendMeta>) {
+    let peers: Vec<PeerId> = socket.connected_peers().collect();
+    for SendMeta(message) in outgoing.read() {
+        for peer in &peers {
+            send(&mut socket, *peer, message);
+        }
+    }
+}
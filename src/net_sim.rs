@@ -0,0 +1,85 @@
+//! Artificial latency, jitter and packet loss on the GGRS socket, for reproducing bad network
+//! conditions (and the rollbacks they cause) on a single machine.
+
+use std::time::Duration;
+
+use bevy::{platform::time::Instant, prelude::*};
+use bevy_ggrs::ggrs::{Message, NonBlockingSocket};
+use bevy_matchbox::prelude::PeerId;
+
+/// How bad to make the network, from the `--sim-*` command line flags
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetworkSimulation {
+    pub latency_ms: u32,
+    /// Each message is delayed by up to this much more, on top of `latency_ms`
+    pub jitter_ms: u32,
+    /// Chance from 0 to 1 that a message is dropped
+    pub packet_loss: f32,
+}
+
+/// Wraps a socket, holding on to outgoing messages until they're "delivered"
+pub struct SimulatedSocket<S> {
+    socket: S,
+    simulation: NetworkSimulation,
+    in_flight: Vec<(Instant, PeerId, Message)>,
+    rng_state: u64,
+}
+
+impl<S> SimulatedSocket<S> {
+    pub fn new(socket: S, simulation: Option<NetworkSimulation>) -> Self {
+        SimulatedSocket {
+            socket,
+            simulation: simulation.unwrap_or_default(),
+            in_flight: Vec::new(),
+            // Doesn't need to be deterministic, just not the same every run
+            rng_state: uuid::Uuid::new_v4().as_u64_pair().0 | 1,
+        }
+    }
+
+    /// xorshift, returns a value in `0.0..1.0`
+    fn random(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        (self.rng_state >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+impl<S: NonBlockingSocket<PeerId>> SimulatedSocket<S> {
+    fn flush(&mut self) {
+        let now = Instant::now();
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            if self.in_flight[i].0 <= now {
+                let (_, addr, msg) = self.in_flight.swap_remove(i);
+                self.socket.send_to(&msg, &addr);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+impl<S: NonBlockingSocket<PeerId>> NonBlockingSocket<PeerId> for SimulatedSocket<S> {
+    fn send_to(&mut self, msg: &Message, addr: &PeerId) {
+        if self.simulation == NetworkSimulation::default() {
+            self.socket.send_to(msg, addr);
+            return;
+        }
+
+        if self.random() < self.simulation.packet_loss {
+            return;
+        }
+
+        let jitter = (self.random() * self.simulation.jitter_ms as f32) as u64;
+        let delay = Duration::from_millis(self.simulation.latency_ms as u64 + jitter);
+        self.in_flight
+            .push((Instant::now() + delay, *addr, msg.clone()));
+        self.flush();
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        self.flush();
+        self.socket.receive_all_messages()
+    }
+}
@@ -0,0 +1,416 @@
+//! Headless observer mode.
+//!
+//! An observer joins a room as a GGRS spectator, runs the same simulation as the players and
+//! serves the current match state as JSON over HTTP for scoreboards and stream overlays.
+//!
+//! GGRS spectators have to be added by a player when the session is built, and that player has to
+//! be reachable over the same socket it uses for GGRS. Since the players' matchbox room is already
+//! full, observers instead wait in a separate observer room which every player joins while in the
+//! lobby. It's keyed by the physics fingerprint like the players' room, so observers only ever
+//! watch matches they can simulate. Observers say so with [`MetaMessage::ObserverJoin`], and when
+//! the session starts the host (handle 0) adds just them as spectators and tells them so, the
+//! other players just leave it.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{platform::time::Instant, prelude::*};
+use bevy_ggrs::{
+    RollbackFrameCount,
+    ggrs::{Message, NonBlockingSocket, PlayerType},
+    prelude::*,
+};
+use bevy_matchbox::{
+    MatchboxSocket,
+    matchbox_socket::{WebRtcChannel, WebRtcSocket},
+    prelude::*,
+};
+use serde::Serialize;
+
+use crate::{
+    GameState,
+    game::{self, Player, RollbackState, Scores},
+    lobby_config::LobbyConfig,
+    net_messages::{self, GGRS_CHANNEL, MetaMessage},
+    net_sim::SimulatedSocket,
+    physics_config::PhysicsConfig,
+};
+
+/// Set when running as an observer instead of a player
+#[derive(Resource, Clone, Debug)]
+pub struct ObserverConfig {
+    /// Address to serve [`MatchState`] on
+    pub http_addr: String,
+    /// How far behind live [`MatchState`] is served, so nobody can stream-snipe from it. The
+    /// session keeps up with the host as usual, only what's published lags.
+    pub delay_secs: f32,
+}
+
+/// Socket connected to the room's observer room, separate from the game's [`MatchboxSocket`]
+#[derive(Resource, Deref, DerefMut)]
+pub struct ObserverSocket {
+    #[deref]
+    socket: MatchboxSocket,
+    /// Peers that sent [`MetaMessage::ObserverJoin`]. Everyone else in the room is another
+    /// player waiting in the same lobby.
+    observers: Vec<PeerId>,
+}
+
+/// JSON served to overlays. Rebuilt every rendered frame, which for a spectator only ever shows
+/// confirmed frames, so overlays can poll as often as they like.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MatchState {
+    /// [`MatchId`](game::MatchId) as hex, a JSON number can't hold all 64 bits
+    pub match_id: String,
+    /// GGRS frame the state is from
+    pub frame: i32,
+    /// Rounds started so far this match, starting at 1
+    pub round: u32,
+    /// `"InRound"` while players are racing around, `"RoundEnd"` between rounds, `"MatchEnd"` once
+    /// the match is won
+    pub round_state: String,
+    pub players: Vec<PlayerState>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayerState {
+    pub handle: usize,
+    pub score: u32,
+    pub alive: bool,
+}
+
+/// Latest [`MatchState`] serialized to JSON, shared with the HTTP thread
+#[derive(Resource, Clone, Default)]
+struct PublishedMatchState(Arc<Mutex<String>>);
+
+/// States waiting out [`ObserverConfig::delay_secs`], oldest first, with when they were built
+#[derive(Resource, Default)]
+struct DelayedMatchStates(VecDeque<(Instant, String)>);
+
+/// Rounds started so far in the match being observed
+#[derive(Resource, Default)]
+struct ObservedRound(u32);
+
+pub struct ObserverPlugin;
+
+impl Plugin for ObserverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PublishedMatchState>()
+            .init_resource::<DelayedMatchStates>()
+            .init_resource::<ObservedRound>()
+            .add_systems(OnEnter(GameState::Lobby), connect_observer_room)
+            .add_systems(OnEnter(GameState::Playing), reset_observed_round)
+            .add_systems(
+                Update,
+                (
+                    wait_for_host.run_if(resource_exists::<ObserverConfig>),
+                    update_observer_peers.run_if(not(resource_exists::<ObserverConfig>)),
+                )
+                    .run_if(in_state(GameState::Lobby))
+                    .run_if(resource_exists::<ObserverSocket>),
+            )
+            .add_systems(
+                Update,
+                publish_match_state
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(resource_exists::<ObserverConfig>),
+            );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            Startup,
+            serve_match_state.run_if(resource_exists::<ObserverConfig>),
+        );
+    }
+}
+
+fn connect_observer_room(
+    mut commands: Commands,
+    lobby_config: Res<LobbyConfig>,
+    physics: Res<PhysicsConfig>,
+    observer_socket: Option<Res<ObserverSocket>>,
+) {
+    if observer_socket.is_some() {
+        return;
+    }
+
+    let url = format!(
+        "{}/{}-{:08x}-observers",
+        lobby_config.server,
+        lobby_config.room,
+        physics.fingerprint()
+    );
+    info!("connecting to observer room: {url:?}");
+
+    commands.insert_resource(ObserverSocket {
+        socket: MatchboxSocket::from(
+            net_messages::with_channels(WebRtcSocket::builder(url)).build(),
+        ),
+        observers: Vec::new(),
+    });
+}
+
+/// Player side of the lobby, keeps track of who's waiting to observe
+fn update_observer_peers(mut observer_socket: ResMut<ObserverSocket>) {
+    let Ok(peer_changes) = observer_socket.try_update_peers() else {
+        warn!("observer socket dropped");
+        return;
+    };
+
+    for (peer, new_state) in peer_changes {
+        if matches!(new_state, PeerState::Disconnected)
+            && let Some(i) = observer_socket.observers.iter().position(|p| *p == peer)
+        {
+            observer_socket.observers.remove(i);
+            info!("observer {peer} disconnected");
+        }
+    }
+
+    for (peer, message) in net_messages::receive(&mut observer_socket) {
+        if message == MetaMessage::ObserverJoin && !observer_socket.observers.contains(&peer) {
+            observer_socket.observers.push(peer);
+            info!("observer {peer} connected");
+        }
+    }
+}
+
+/// GGRS socket for the host, sending to players over the game room and spectators over the
+/// observer room
+struct HostSocket {
+    game: SimulatedSocket<WebRtcChannel>,
+    observers: WebRtcChannel,
+    observer_peers: Vec<PeerId>,
+}
+
+impl NonBlockingSocket<PeerId> for HostSocket {
+    fn send_to(&mut self, msg: &Message, addr: &PeerId) {
+        if self.observer_peers.contains(addr) {
+            self.observers.send_to(msg, addr);
+        } else {
+            self.game.send_to(msg, addr);
+        }
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        let mut messages = self.game.receive_all_messages();
+        messages.extend(self.observers.receive_all_messages());
+        messages
+    }
+}
+
+/// Starts the player's P2P session, adding everyone in the observer room as spectators if we're
+/// the host
+pub fn start_p2p_session(
+    mut sess_build: SessionBuilder<game::GameConfig>,
+    players: &[PlayerType<PeerId>],
+    game_channel: SimulatedSocket<WebRtcChannel>,
+    seed: u64,
+    handicaps: &[game::Handicap],
+    commands: &mut Commands,
+    observer_socket: Option<ResMut<ObserverSocket>>,
+) -> Session<game::GameConfig> {
+    let is_host = matches!(players.first(), Some(PlayerType::Local));
+
+    let Some(mut observer_socket) = observer_socket.filter(|_| is_host) else {
+        // Only the host talks to observers
+        commands.remove_resource::<ObserverSocket>();
+        return Session::P2P(
+            sess_build
+                .start_p2p_session(game_channel)
+                .expect("failed to start session"),
+        );
+    };
+
+    // Only peers still connected, one may have left without us hearing about it yet
+    let connected: Vec<PeerId> = observer_socket.connected_peers().collect();
+    let observer_peers: Vec<PeerId> = observer_socket
+        .observers
+        .iter()
+        .copied()
+        .filter(|peer| connected.contains(peer))
+        .collect();
+    let hello = MetaMessage::ObserverHello {
+        num_players: players.len(),
+        seed,
+        handicaps: handicaps.to_vec(),
+    };
+
+    for (i, peer) in observer_peers.iter().enumerate() {
+        sess_build = sess_build
+            .add_player(PlayerType::Spectator(*peer), players.len() + i)
+            .expect("failed to add spectator");
+        net_messages::send(&mut observer_socket, *peer, &hello);
+    }
+
+    if !observer_peers.is_empty() {
+        info!("hosting {} observer(s)", observer_peers.len());
+    }
+
+    let socket = HostSocket {
+        game: game_channel,
+        observers: observer_socket
+            .take_channel(GGRS_CHANNEL)
+            .expect("observer channel already taken"),
+        observer_peers,
+    };
+
+    Session::P2P(
+        sess_build
+            .start_p2p_session(socket)
+            .expect("failed to start session"),
+    )
+}
+
+/// Observer side of the lobby, waits for the host to add us as a spectator
+fn wait_for_host(
+    mut commands: Commands,
+    mut app_state: ResMut<NextState<GameState>>,
+    mut observer_socket: ResMut<ObserverSocket>,
+) {
+    let Ok(peer_changes) = observer_socket.try_update_peers() else {
+        warn!("observer socket dropped");
+        app_state.set(GameState::LobbyConfig);
+        return;
+    };
+
+    // We don't know which of them is the host yet, so tell everyone
+    for (peer, new_state) in peer_changes {
+        if matches!(new_state, PeerState::Connected) {
+            net_messages::send(&mut observer_socket, peer, &MetaMessage::ObserverJoin);
+        }
+    }
+
+    let Some((host, num_players, seed, handicaps)) = net_messages::receive(&mut observer_socket)
+        .into_iter()
+        .find_map(|(peer, message)| match message {
+            MetaMessage::ObserverHello {
+                num_players,
+                seed,
+                handicaps,
+            } => Some((peer, num_players, seed, handicaps)),
+            _ => None,
+        })
+    else {
+        return;
+    };
+
+    info!("spectating host {host} with {num_players} players");
+
+    let channel = observer_socket
+        .take_channel(GGRS_CHANNEL)
+        .expect("observer channel already taken");
+    let sess = SessionBuilder::<game::GameConfig>::new()
+        .with_num_players(num_players)
+        .start_spectator_session(host, channel);
+
+    commands.insert_resource(Session::Spectator(sess));
+    commands.insert_resource(game::GameRng::new(seed));
+    commands.insert_resource(game::MatchId(seed));
+    commands.insert_resource(game::Handicaps(handicaps));
+    app_state.set(GameState::Playing);
+}
+
+/// Each match counts its rounds from zero again
+fn reset_observed_round(mut round: ResMut<ObservedRound>) {
+    round.0 = 0;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn publish_match_state(
+    config: Res<ObserverConfig>,
+    scores: Res<Scores>,
+    round_state: Res<State<RollbackState>>,
+    match_id: Option<Res<game::MatchId>>,
+    frame: Res<RollbackFrameCount>,
+    players: Query<&Player>,
+    published: Res<PublishedMatchState>,
+    mut delayed: ResMut<DelayedMatchStates>,
+    mut round: ResMut<ObservedRound>,
+) {
+    // Spectators never roll back, so every round start is seen exactly once
+    if round_state.is_changed() && *round_state.get() == RollbackState::InRound {
+        round.0 += 1;
+    }
+
+    let mut handles: Vec<usize> = scores.keys().copied().collect();
+    handles.sort();
+
+    let state = MatchState {
+        match_id: match_id
+            .map(|id| format!("{:016x}", id.0))
+            .unwrap_or_default(),
+        frame: i32::from(*frame),
+        round: round.0,
+        round_state: format!("{:?}", round_state.get()),
+        players: handles
+            .into_iter()
+            .map(|handle| PlayerState {
+                handle,
+                score: scores[&handle],
+                alive: players.iter().any(|p| p.handle == handle),
+            })
+            .collect(),
+    };
+
+    let now = Instant::now();
+    delayed.0.push_back((
+        now,
+        serde_json::to_string(&state).expect("match state is always serializable"),
+    ));
+
+    // Publish the newest state that's been held back long enough
+    let delay = Duration::from_secs_f32(config.delay_secs);
+    let mut ready = None;
+    while delayed
+        .0
+        .front()
+        .is_some_and(|(built, _)| now.duration_since(*built) >= delay)
+    {
+        ready = delayed.0.pop_front();
+    }
+
+    if let Some((_, json)) = ready {
+        *published.0.lock().unwrap() = json;
+    }
+}
+
+/// Serves the latest [`MatchState`] to anyone who asks, on its own thread
+#[cfg(not(target_arch = "wasm32"))]
+fn serve_match_state(config: Res<ObserverConfig>, published: Res<PublishedMatchState>) {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    let listener = match TcpListener::bind(&config.http_addr) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("failed to serve match state on {}: {err}", config.http_addr);
+            return;
+        }
+    };
+    info!("serving match state on http://{}", config.http_addr);
+
+    let published = published.0.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            // We answer every request the same way, so just drain what we can of it
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+
+            let body = published.lock().unwrap().clone();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 Access-Control-Allow-Origin: *\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+}
@@ -0,0 +1,493 @@
+//! Overlay opened with Escape during a match. The match keeps running underneath, it only gives a
+//! way out, and makes sure leaving is deliberate since it disconnects everyone else too. None of
+//! our keys reach the match while it's open, see
+//! [`read_local_inputs`](crate::game::read_local_inputs).
+//!
+//! It also has a settings screen for anything that's only presentation. Match rules from
+//! [`PhysicsConfig`] are shown but can't be changed, every peer has to keep the same ones.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    GameState,
+    lobby_config::button,
+    music::MUSIC_TRACKS,
+    physics_config::PhysicsConfig,
+    settings::{
+        AccessibilitySettings, AudioSettings, CameraMode, CameraSettings, ControlSettings,
+        DeathEffects, GraphicsSettings, HoverKey, HudSettings,
+    },
+};
+
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PauseMenu {
+    #[default]
+    Closed,
+    Open {
+        selected: usize,
+    },
+    ConfirmLeave {
+        selected: usize,
+    },
+    Settings {
+        selected: usize,
+    },
+}
+
+impl PauseMenu {
+    fn actions(&self) -> &'static [PauseAction] {
+        match self {
+            PauseMenu::Closed => &[],
+            PauseMenu::Open { .. } => &[
+                PauseAction::Resume,
+                PauseAction::Settings,
+                PauseAction::Leave,
+            ],
+            // Cancel first so a stray Enter doesn't disconnect anyone
+            PauseMenu::ConfirmLeave { .. } => &[PauseAction::Cancel, PauseAction::ConfirmLeave],
+            PauseMenu::Settings { .. } => &[
+                PauseAction::Toggle(Toggle::ReducedMotion),
+                PauseAction::Toggle(Toggle::ScreenShake),
+                PauseAction::Toggle(Toggle::SpawnIndicators),
+                PauseAction::Toggle(Toggle::DeathEffects),
+                PauseAction::Toggle(Toggle::Scoreboard),
+                PauseAction::Toggle(Toggle::Camera),
+                PauseAction::Volume(VolumeSetting::Master),
+                PauseAction::Volume(VolumeSetting::Sfx),
+                PauseAction::Volume(VolumeSetting::Music),
+                PauseAction::Toggle(Toggle::Music),
+                PauseAction::Toggle(Toggle::Haptics),
+                PauseAction::Toggle(Toggle::AnalogTurn),
+                PauseAction::Toggle(Toggle::HoverKey),
+                PauseAction::Back,
+            ],
+        }
+    }
+
+    fn selected(&self) -> Option<usize> {
+        match *self {
+            PauseMenu::Closed => None,
+            PauseMenu::Open { selected }
+            | PauseMenu::ConfirmLeave { selected }
+            | PauseMenu::Settings { selected } => Some(selected),
+        }
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            PauseMenu::Closed => None,
+            PauseMenu::Open { selected }
+            | PauseMenu::ConfirmLeave { selected }
+            | PauseMenu::Settings { selected } => Some(selected),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum PauseAction {
+    Resume,
+    Leave,
+    ConfirmLeave,
+    Cancel,
+    Settings,
+    Back,
+    Toggle(Toggle),
+    Volume(VolumeSetting),
+}
+
+/// Presentation settings that can be flipped from the pause menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Toggle {
+    ReducedMotion,
+    ScreenShake,
+    SpawnIndicators,
+    DeathEffects,
+    Scoreboard,
+    Camera,
+    Music,
+    Haptics,
+    AnalogTurn,
+    HoverKey,
+}
+
+/// Volumes turned up and down a [`VOLUME_STEP`] at a time from the pause menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum VolumeSetting {
+    Master,
+    Sfx,
+    Music,
+}
+
+const VOLUME_STEP: f32 = 0.1;
+
+/// The settings the pause menu can change, none of which affect the simulation
+#[derive(SystemParam)]
+struct MenuSettings<'w> {
+    graphics: ResMut<'w, GraphicsSettings>,
+    accessibility: ResMut<'w, AccessibilitySettings>,
+    hud: ResMut<'w, HudSettings>,
+    camera: ResMut<'w, CameraSettings>,
+    audio: ResMut<'w, AudioSettings>,
+    controls: ResMut<'w, ControlSettings>,
+}
+
+impl MenuSettings<'_> {
+    fn label(&self, toggle: Toggle) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match toggle {
+            Toggle::ReducedMotion => {
+                format!(
+                    "Reduced Motion: {}",
+                    on_off(self.accessibility.reduced_motion)
+                )
+            }
+            Toggle::ScreenShake => {
+                format!("Screen Shake: {}", on_off(self.graphics.screen_shake > 0.0))
+            }
+            Toggle::SpawnIndicators => {
+                format!("Spawn Arrows: {}", on_off(self.graphics.spawn_indicators))
+            }
+            Toggle::DeathEffects => format!("Death Effects: {:?}", self.graphics.death_effects),
+            Toggle::Scoreboard => format!("Scoreboard: {}", on_off(self.hud.show_scoreboard)),
+            Toggle::Camera => format!("Camera: {:?}", self.camera.mode),
+            Toggle::Music => format!(
+                "Music: {}",
+                MUSIC_TRACKS[self.audio.match_track % MUSIC_TRACKS.len()].0
+            ),
+            Toggle::Haptics => format!("Rumble: {}", on_off(self.controls.haptics)),
+            Toggle::AnalogTurn => format!("Analog Turning: {}", on_off(self.controls.analog_turn)),
+            Toggle::HoverKey => format!("Hover: {:?}", self.controls.hover_key),
+        }
+    }
+
+    fn volume_label(&self, volume: VolumeSetting) -> String {
+        let name = match volume {
+            VolumeSetting::Master => "Volume",
+            VolumeSetting::Sfx => "Effects Volume",
+            VolumeSetting::Music => "Music Volume",
+        };
+        format!("{name}: {:.0}%", self.volume(volume) * 100.0)
+    }
+
+    fn volume(&self, volume: VolumeSetting) -> f32 {
+        match volume {
+            VolumeSetting::Master => self.audio.master,
+            VolumeSetting::Sfx => self.audio.sfx,
+            VolumeSetting::Music => self.audio.music,
+        }
+    }
+
+    /// Turns `volume` up or down by `steps`, landing on a whole step and staying within 0 to 1
+    fn step_volume(&mut self, volume: VolumeSetting, steps: f32) {
+        let value = ((self.volume(volume) / VOLUME_STEP).round() + steps) * VOLUME_STEP;
+        let value = value.clamp(0.0, 1.0);
+        match volume {
+            VolumeSetting::Master => self.audio.master = value,
+            VolumeSetting::Sfx => self.audio.sfx = value,
+            VolumeSetting::Music => self.audio.music = value,
+        }
+    }
+
+    fn toggle(&mut self, toggle: Toggle) {
+        match toggle {
+            Toggle::ReducedMotion => {
+                self.accessibility.reduced_motion = !self.accessibility.reduced_motion;
+            }
+            Toggle::ScreenShake => {
+                self.graphics.screen_shake = if self.graphics.screen_shake > 0.0 {
+                    0.0
+                } else {
+                    GraphicsSettings::default().screen_shake
+                };
+            }
+            Toggle::SpawnIndicators => {
+                self.graphics.spawn_indicators = !self.graphics.spawn_indicators;
+            }
+            Toggle::DeathEffects => {
+                self.graphics.death_effects = match self.graphics.death_effects {
+                    DeathEffects::Full => DeathEffects::Simple,
+                    DeathEffects::Simple => DeathEffects::Off,
+                    DeathEffects::Off => DeathEffects::Full,
+                };
+            }
+            Toggle::Scoreboard => self.hud.show_scoreboard = !self.hud.show_scoreboard,
+            Toggle::Camera => {
+                self.camera.mode = match self.camera.mode {
+                    CameraMode::Follow => CameraMode::Overview,
+                    CameraMode::Overview => CameraMode::Follow,
+                };
+            }
+            Toggle::Music => {
+                self.audio.match_track = (self.audio.match_track + 1) % MUSIC_TRACKS.len();
+            }
+            Toggle::Haptics => self.controls.haptics = !self.controls.haptics,
+            Toggle::AnalogTurn => self.controls.analog_turn = !self.controls.analog_turn,
+            Toggle::HoverKey => {
+                self.controls.hover_key = match self.controls.hover_key {
+                    HoverKey::Jump => HoverKey::Shift,
+                    HoverKey::Shift => HoverKey::C,
+                    HoverKey::C => HoverKey::Jump,
+                };
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct PauseMenuEntity;
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseMenu>()
+            .add_systems(OnExit(GameState::Playing), close_pause_menu)
+            .add_systems(
+                Update,
+                (
+                    pause_menu_keyboard,
+                    pause_menu_buttons,
+                    spawn_pause_menu,
+                    highlight_selected,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+fn close_pause_menu(mut menu: ResMut<PauseMenu>) {
+    *menu = PauseMenu::Closed;
+}
+
+fn pause_menu_keyboard(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut menu: ResMut<PauseMenu>,
+    mut app_state: ResMut<NextState<GameState>>,
+    mut settings: MenuSettings,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        *menu = match *menu {
+            PauseMenu::Closed => PauseMenu::Open { selected: 0 },
+            PauseMenu::Open { .. } => PauseMenu::Closed,
+            PauseMenu::ConfirmLeave { .. } => PauseMenu::Open { selected: 2 },
+            PauseMenu::Settings { .. } => PauseMenu::Open { selected: 1 },
+        };
+        return;
+    }
+
+    let actions = menu.actions();
+    let Some(selected) = menu.selected() else {
+        return;
+    };
+
+    let mut new_selected = selected;
+    // Left/right are for turning volumes up and down, so only navigate with up/down
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        new_selected = (new_selected + actions.len() - 1) % actions.len();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowDown, KeyCode::Tab]) {
+        new_selected = (new_selected + 1) % actions.len();
+    }
+
+    if new_selected != selected
+        && let Some(selected) = menu.selected_mut()
+    {
+        *selected = new_selected;
+    }
+
+    if let PauseAction::Volume(volume) = actions[new_selected] {
+        if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+            settings.step_volume(volume, -1.0);
+        }
+        if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+            settings.step_volume(volume, 1.0);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        apply_action(
+            actions[new_selected],
+            &mut menu,
+            &mut app_state,
+            &mut settings,
+        );
+    }
+}
+
+fn pause_menu_buttons(
+    interactions: Query<(&Interaction, &PauseAction), Changed<Interaction>>,
+    mut menu: ResMut<PauseMenu>,
+    mut app_state: ResMut<NextState<GameState>>,
+    mut settings: MenuSettings,
+) {
+    for (interaction, action) in &interactions {
+        if *interaction == Interaction::Pressed {
+            apply_action(*action, &mut menu, &mut app_state, &mut settings);
+        }
+    }
+}
+
+fn apply_action(
+    action: PauseAction,
+    menu: &mut PauseMenu,
+    app_state: &mut NextState<GameState>,
+    settings: &mut MenuSettings,
+) {
+    match action {
+        PauseAction::Resume => *menu = PauseMenu::Closed,
+        PauseAction::Leave => *menu = PauseMenu::ConfirmLeave { selected: 0 },
+        PauseAction::Cancel => *menu = PauseMenu::Open { selected: 2 },
+        PauseAction::ConfirmLeave => {
+            // lobby_config_setup tears down the match, see `teardown_match`
+            *menu = PauseMenu::Closed;
+            app_state.set(GameState::LobbyConfig);
+        }
+        PauseAction::Settings => *menu = PauseMenu::Settings { selected: 0 },
+        PauseAction::Back => *menu = PauseMenu::Open { selected: 1 },
+        // Saved by save_settings like any other change
+        PauseAction::Toggle(toggle) => settings.toggle(toggle),
+        // Clicking or Enter turns it up a step, going round to muted after full
+        PauseAction::Volume(volume) => {
+            if settings.volume(volume) >= 1.0 {
+                settings.step_volume(volume, -1.0 / VOLUME_STEP);
+            } else {
+                settings.step_volume(volume, 1.0);
+            }
+        }
+    }
+}
+
+/// Label of each action's button
+fn action_label(action: PauseAction, settings: &MenuSettings) -> String {
+    match action {
+        PauseAction::Resume => "Resume".to_string(),
+        PauseAction::Leave => "Leave Match".to_string(),
+        PauseAction::ConfirmLeave => "Leave".to_string(),
+        PauseAction::Cancel => "Cancel".to_string(),
+        PauseAction::Settings => "Settings".to_string(),
+        PauseAction::Back => "Back".to_string(),
+        PauseAction::Toggle(toggle) => settings.label(toggle),
+        PauseAction::Volume(volume) => settings.volume_label(volume),
+    }
+}
+
+/// Rebuilds the overlay whenever the menu changes screens, or a setting on it changes
+fn spawn_pause_menu(
+    mut commands: Commands,
+    menu: Res<PauseMenu>,
+    settings: MenuSettings,
+    physics: Res<PhysicsConfig>,
+    existing: Query<Entity, With<PauseMenuEntity>>,
+    mut last_screen: Local<Option<(std::mem::Discriminant<PauseMenu>, Vec<String>)>>,
+) {
+    let labels: Vec<String> = menu
+        .actions()
+        .iter()
+        .map(|action| action_label(*action, &settings))
+        .collect();
+    let screen = (std::mem::discriminant(&*menu), labels);
+    if last_screen.as_ref() == Some(&screen) {
+        return;
+    }
+    let labels = screen.1.clone();
+    *last_screen = Some(screen);
+
+    for entity in &existing {
+        commands.entity(entity).despawn();
+    }
+
+    let title = match *menu {
+        PauseMenu::Closed => return,
+        PauseMenu::Open { .. } => "Paused",
+        PauseMenu::ConfirmLeave { .. } => "Leave match? Others are still playing",
+        PauseMenu::Settings { .. } => "Settings",
+    };
+    let is_settings = matches!(*menu, PauseMenu::Settings { .. });
+
+    commands
+        .spawn((
+            PauseMenuEntity,
+            DespawnOnExit(GameState::Playing),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: if is_settings { px(6) } else { px(16) },
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.6)),
+            GlobalZIndex(10),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font_size: 48.,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+
+            for (action, label) in menu.actions().iter().zip(labels) {
+                let mut button = parent.spawn(button(label, *action));
+                if is_settings {
+                    // Settings labels don't fit the usual button
+                    button.entry::<Node>().and_modify(|mut node| {
+                        node.width = px(360);
+                        node.height = px(40);
+                    });
+                }
+            }
+
+            if is_settings {
+                // Greyed out, every peer has to keep playing by the same rules
+                let locked = [
+                    "Match rules are locked until the match ends".to_string(),
+                    format!(
+                        "Mode: {}",
+                        if physics.casual.is_some() {
+                            "Casual"
+                        } else {
+                            "Classic"
+                        }
+                    ),
+                    format!("Drift: {}", if physics.drift { "On" } else { "Off" }),
+                    format!(
+                        "Shuffle Spawns: {}",
+                        if physics.shuffle_spawns { "On" } else { "Off" }
+                    ),
+                    format!("Round Transition: {:?}", physics.round_transition),
+                ];
+                for line in locked {
+                    parent.spawn((
+                        Text::new(line),
+                        TextFont {
+                            font_size: 20.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb(0.5, 0.5, 0.5)),
+                    ));
+                }
+            }
+        });
+}
+
+fn highlight_selected(menu: Res<PauseMenu>, mut buttons: Query<(&PauseAction, &mut BorderColor)>) {
+    let actions = menu.actions();
+    let Some(selected) = menu.selected().map(|selected| actions[selected]) else {
+        return;
+    };
+
+    for (action, mut border) in &mut buttons {
+        let color = if *action == selected {
+            Color::srgb(1.0, 0.8, 0.0)
+        } else {
+            Color::WHITE
+        };
+        border.set_if_neq(BorderColor::all(color));
+    }
+}
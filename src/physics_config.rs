@@ -0,0 +1,278 @@
+//! Tunables that change the simulation.
+//!
+//! Unlike [`settings`](crate::settings), every peer has to run with the exact same values or the
+//! simulation desyncs. Peers only get matched with others using the same config, see
+//! [`PhysicsConfig::fingerprint`].
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::FPS;
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    /// Frames after spawning during which a player can't die
+    pub spawn_grace_frames: u32,
+    /// Whether turning while dashing carries momentum, making players slide through sharp turns
+    pub drift: bool,
+    /// How quickly, per second, a drifting player's turn rate catches up with their input
+    pub drift_grip: f32,
+    /// How far a player moves before dropping the next trail segment. Larger is fewer entities
+    /// and cheaper collisions on weak hardware, but blockier trails. Each segment stretches back
+    /// to the one before it and collides along its whole length, so moving further than this in
+    /// one frame (like dashing) never leaves gaps.
+    pub trail_spawn_dist: f32,
+    /// Once the match has gone on this long, it ends after the current round if someone is in
+    /// the lead, otherwise it goes to sudden death until someone is. 0 for no limit.
+    pub match_time_limit_secs: u32,
+    /// The match ends after the round where someone reaches this score, as long as nobody is
+    /// tied with them. 0 for no target.
+    pub victory_score: u32,
+    /// Hand out spawn points randomly each round instead of always by handle
+    pub shuffle_spawns: bool,
+    pub round_transition: RoundTransition,
+    /// Frames at the start of each round before anyone can move, counted down on screen. The
+    /// round intro sweep plays during it. 0 starts straight away.
+    pub round_countdown_frames: u32,
+    /// Speed multiplier for the last two players alive, when the round started with more.
+    /// 1 turns it off.
+    pub last_stand_speed: f32,
+    /// Frames after a player dies until their trail disappears, opening the arena back up.
+    /// `None` keeps trails for the whole round.
+    pub dead_trail_decay_frames: Option<u32>,
+    /// Frames over which each trail segment thins down to nothing after it's left, so walls only
+    /// last a while. `None` keeps segments full size.
+    pub trail_shrink_frames: Option<u32>,
+    /// Chance per second that a trail eraser pickup appears while there isn't one already.
+    /// 0 turns them off.
+    pub eraser_spawn_chance: f32,
+    /// Trails within this distance of whoever picks up an eraser are erased
+    pub eraser_radius: f32,
+    /// Highest a player can get above the surface. A full jump peaks at about 1.7 and nothing
+    /// else pushes players up, so this only matters if those change.
+    pub max_altitude: f32,
+    /// What happens when two players run into each other
+    pub player_collision: PlayerCollision,
+    /// What a dash costs, so it can't be used every frame
+    pub dash_cost: DashCost,
+    /// Fuel a dash uses up when [`dash_cost`](Self::dash_cost) takes fuel, out of 100
+    pub dash_fuel_cost: f32,
+    /// Fraction of the normal speed a player slows to while holding brake, never below
+    /// [`min_move_speed`](Self::min_move_speed)
+    pub brake_factor: f32,
+    /// Slowest a player can move over the surface, in units per second (normal speed is 5),
+    /// after braking, handicaps and everything else that slows them. Moving keeps the trail
+    /// coming, so this stops anyone camping behind it. 0 lets players stop.
+    pub min_move_speed: f32,
+    /// How far over the surface a full dash goes, with the dash speed worked out to cover it in
+    /// the usual dash time. `None` dashes at a fixed multiple of the normal speed instead.
+    pub dash_distance: Option<f32>,
+    /// How hard the wind pushes players around the sphere, in units per second. The wind's
+    /// pattern changes each round. 0 for no wind.
+    pub wind_strength: f32,
+    /// Number of boost pads scattered over the arena, placed the same way for everyone from the
+    /// match's seed. 0 for none.
+    pub boost_pads: u32,
+    /// Speed multiplier while boosted by a pad
+    pub boost_pad_speed: f32,
+    /// Frames a pad's boost lasts
+    pub boost_pad_frames: u32,
+    /// Between rounds, everyone votes on a [`RoundModifier`](crate::game::RoundModifier) for the
+    /// next round. Does nothing with [`RoundTransition::Instant`], there's no time to vote.
+    pub modifier_vote: bool,
+    /// Eliminated players come back instead of waiting out the round, rounds run on a timer and
+    /// score a point per kill. `None` is the usual last one standing format.
+    pub casual: Option<CasualRules>,
+    /// Times each player can pause the round for everyone per match, like calling a timeout.
+    /// 0 turns pausing off.
+    pub pauses_per_player: u32,
+    /// Frames a pause lasts unless whoever called it resumes early
+    pub pause_frames: u32,
+    /// Extra points for whoever is in last place when they finish a round in the top half, so
+    /// a runaway leader can still be caught. 0 turns it off.
+    pub comeback_bonus: u32,
+    /// Only part of the sphere is in play, for modes like king of the hill. `None` is the whole
+    /// sphere.
+    pub arena_band: Option<ArenaBand>,
+}
+
+/// The part of the sphere between two latitudes, see [`PhysicsConfig::arena_band`]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArenaBand {
+    /// Bottom edge in degrees, from -90 at the bottom pole to 90 at the top
+    pub min_latitude: f32,
+    /// Top edge in degrees, 90 for a cap around the top pole
+    pub max_latitude: f32,
+    /// What happens to a player who goes over either edge
+    pub edge: ArenaEdge,
+}
+
+impl Default for ArenaBand {
+    fn default() -> Self {
+        ArenaBand {
+            min_latitude: -45.0,
+            max_latitude: 45.0,
+            edge: ArenaEdge::default(),
+        }
+    }
+}
+
+/// What happens to a player who goes over the edge of an [`ArenaBand`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArenaEdge {
+    /// They're out, like running into their own trail
+    #[default]
+    Kill,
+    /// They're turned back in, like bouncing off a wall
+    Bounce,
+}
+
+/// How the pause between rounds ends
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundTransition {
+    /// Start the next round right away
+    Instant,
+    /// Start the next round after a short pause
+    #[default]
+    Delayed,
+    /// Wait until every player has pressed continue
+    Manual,
+}
+
+/// What happens when two players run into each other
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerCollision {
+    /// Players pass through each other, only trails kill
+    #[default]
+    Off,
+    /// Both players die
+    BothDie,
+    /// A dashing player runs over one that isn't, otherwise both die
+    DashWins,
+}
+
+/// What stops players from dashing all the time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashCost {
+    /// Wait for a cooldown after each dash
+    #[default]
+    Cooldown,
+    /// Spend hover fuel on each dash, so hovering and dashing share one pool
+    Fuel,
+    /// Both wait for the cooldown and spend fuel
+    Both,
+}
+
+impl DashCost {
+    pub fn uses_cooldown(self) -> bool {
+        matches!(self, DashCost::Cooldown | DashCost::Both)
+    }
+
+    pub fn uses_fuel(self) -> bool {
+        matches!(self, DashCost::Fuel | DashCost::Both)
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            spawn_grace_frames: 0,
+            drift: false,
+            drift_grip: 6.0,
+            // Half the trail radius
+            trail_spawn_dist: 0.1,
+            match_time_limit_secs: 0,
+            victory_score: 0,
+            shuffle_spawns: false,
+            round_transition: RoundTransition::default(),
+            round_countdown_frames: 0,
+            last_stand_speed: 1.0,
+            dead_trail_decay_frames: None,
+            trail_shrink_frames: None,
+            eraser_spawn_chance: 0.0,
+            eraser_radius: 2.0,
+            max_altitude: 2.0,
+            player_collision: PlayerCollision::default(),
+            dash_cost: DashCost::default(),
+            dash_fuel_cost: 30.0,
+            brake_factor: 0.5,
+            min_move_speed: 1.5,
+            dash_distance: None,
+            wind_strength: 0.0,
+            boost_pads: 0,
+            boost_pad_speed: 1.6,
+            boost_pad_frames: 45,
+            modifier_vote: false,
+            casual: None,
+            pauses_per_player: 0,
+            pause_frames: 30 * FPS as u32,
+            comeback_bonus: 0,
+            arena_band: None,
+        }
+    }
+}
+
+/// How a [`PhysicsConfig::casual`] match runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CasualRules {
+    /// Frames between dying and coming back
+    pub respawn_delay_frames: u32,
+    /// Frames each round lasts
+    pub round_frames: u32,
+}
+
+impl Default for CasualRules {
+    fn default() -> Self {
+        CasualRules {
+            respawn_delay_frames: 3 * FPS as u32,
+            round_frames: 90 * FPS as u32,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    /// Smallest allowed [`trail_spawn_dist`](Self::trail_spawn_dist), below this the number of
+    /// segments gets out of hand
+    pub const MIN_TRAIL_SPAWN_DIST: f32 = 0.05;
+
+    pub fn trail_spawn_dist(&self) -> f32 {
+        self.trail_spawn_dist.max(Self::MIN_TRAIL_SPAWN_DIST)
+    }
+
+    /// [`arena_band`](Self::arena_band) with its latitudes on the sphere and the right way up, so
+    /// a hand-edited band can't leave every spawn outside it
+    pub fn arena_band(&self) -> Option<ArenaBand> {
+        self.arena_band.map(|band| {
+            let a = band.min_latitude.clamp(-90.0, 90.0);
+            let b = band.max_latitude.clamp(-90.0, 90.0);
+            ArenaBand {
+                min_latitude: a.min(b),
+                max_latitude: a.max(b),
+                ..band
+            }
+        })
+    }
+
+    /// Whether there's a [`modifier_vote`](Self::modifier_vote) between rounds
+    pub fn modifier_voting(&self) -> bool {
+        self.modifier_vote && self.round_transition != RoundTransition::Instant
+    }
+
+    /// Stable hash of the config, the same on every platform.
+    ///
+    /// It's added to the matchbox room so only peers that agree on the config, and on whether
+    /// they use the `deterministic-math` feature, end up together.
+    pub fn fingerprint(&self) -> u32 {
+        let mut json = serde_json::to_vec(self).expect("physics config is always serializable");
+        // Peers using different math libraries can't stay in sync either
+        json.push(cfg!(feature = "deterministic-math") as u8);
+
+        // FNV-1a, std's hashers aren't guaranteed to be stable across builds
+        json.iter().fold(0x811c9dc5, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x01000193)
+        })
+    }
+}
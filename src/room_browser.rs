@@ -0,0 +1,192 @@
+//! Room discovery on top of a stock matchbox server.
+//!
+//! Matchbox has no way to list rooms, so we use a convention instead: every client also joins a
+//! shared [`DIRECTORY_ROOM`] (a full-mesh room, no `next` param) and, while waiting in a game room,
+//! periodically announces that room to everyone else in the directory. Browsing clients collect
+//! those announcements into [`Rooms`].
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use bevy_matchbox::{MatchboxSocket, matchbox_socket::WebRtcSocket, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, lobby_config::LobbyConfig};
+
+/// Room every client joins to find out about other rooms
+const DIRECTORY_ROOM: &str = "galaxy_cats_directory";
+/// How often a waiting client re-announces its room
+const ANNOUNCE_INTERVAL: f32 = 1.0;
+/// Announcements older than this are dropped from the list
+const ANNOUNCE_TTL: f32 = 3.5;
+
+/// What a waiting client tells the directory about the room it's in
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomAnnouncement {
+    pub room: String,
+    pub players: usize,
+    pub max_players: usize,
+}
+
+/// A room as shown in the browser
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomInfo {
+    pub room: String,
+    pub players: usize,
+    pub max_players: usize,
+}
+
+/// Currently known open rooms, sorted by name
+#[derive(Resource, Default, Clone, Debug, PartialEq, Eq, Deref)]
+pub struct Rooms(Vec<RoomInfo>);
+
+/// Socket connected to the [`DIRECTORY_ROOM`], separate from the game's [`MatchboxSocket`]
+#[derive(Resource, Deref, DerefMut)]
+struct DirectorySocket(MatchboxSocket);
+
+/// Latest announcement from each directory peer, with the time it was received
+#[derive(Resource, Default)]
+struct Announcements(HashMap<PeerId, (RoomAnnouncement, f32)>);
+
+#[derive(Resource, Deref, DerefMut)]
+struct AnnounceTimer(Timer);
+
+impl Default for AnnounceTimer {
+    fn default() -> Self {
+        AnnounceTimer(Timer::from_seconds(ANNOUNCE_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+pub struct RoomBrowserPlugin;
+
+impl Plugin for RoomBrowserPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rooms>()
+            .init_resource::<Announcements>()
+            .init_resource::<AnnounceTimer>()
+            .add_systems(OnEnter(GameState::LobbyConfig), connect_directory)
+            .add_systems(OnEnter(GameState::Playing), disconnect_directory)
+            .add_systems(
+                Update,
+                (
+                    receive_announcements,
+                    announce_room.run_if(in_state(GameState::Lobby)),
+                )
+                    .chain()
+                    .run_if(resource_exists::<DirectorySocket>),
+            );
+    }
+}
+
+fn connect_directory(
+    mut commands: Commands,
+    lobby_config: Res<LobbyConfig>,
+    directory: Option<Res<DirectorySocket>>,
+) {
+    if directory.is_some() || lobby_config.server.is_empty() {
+        return;
+    }
+
+    let url = format!("{}/{DIRECTORY_ROOM}", lobby_config.server);
+    info!("connecting to room directory: {url:?}");
+
+    commands.insert_resource(DirectorySocket(MatchboxSocket::from(
+        WebRtcSocket::builder(url).add_reliable_channel().build(),
+    )));
+}
+
+fn disconnect_directory(
+    mut commands: Commands,
+    directory: Option<ResMut<DirectorySocket>>,
+    mut announcements: ResMut<Announcements>,
+    mut rooms: ResMut<Rooms>,
+) {
+    if let Some(mut directory) = directory {
+        directory.close();
+        commands.remove_resource::<DirectorySocket>();
+    }
+
+    announcements.0.clear();
+    rooms.set_if_neq(Rooms::default());
+}
+
+fn receive_announcements(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut directory: ResMut<DirectorySocket>,
+    mut announcements: ResMut<Announcements>,
+    mut rooms: ResMut<Rooms>,
+) {
+    let now = time.elapsed_secs();
+
+    let Ok(peer_changes) = directory.try_update_peers() else {
+        warn!("room directory socket dropped");
+        commands.remove_resource::<DirectorySocket>();
+        return;
+    };
+
+    for (peer, new_state) in peer_changes {
+        if new_state == PeerState::Disconnected {
+            announcements.0.remove(&peer);
+        }
+    }
+
+    for (peer, packet) in directory.channel_mut(0).receive() {
+        match serde_json::from_slice::<RoomAnnouncement>(&packet) {
+            Ok(announcement) => {
+                announcements.0.insert(peer, (announcement, now));
+            }
+            Err(err) => warn!("bad room announcement from {peer}: {err}"),
+        }
+    }
+
+    announcements
+        .0
+        .retain(|_, (_, received_at)| now - *received_at < ANNOUNCE_TTL);
+
+    // Every member of a room announces it, so merge by name and trust the highest count
+    let mut merged = HashMap::<&str, RoomInfo>::new();
+    for (announcement, _) in announcements.0.values() {
+        let info = merged
+            .entry(announcement.room.as_str())
+            .or_insert_with(|| RoomInfo {
+                room: announcement.room.clone(),
+                players: 0,
+                max_players: announcement.max_players,
+            });
+        info.players = info.players.max(announcement.players);
+    }
+
+    let mut list: Vec<RoomInfo> = merged.into_values().collect();
+    list.sort_by(|a, b| a.room.cmp(&b.room));
+    rooms.set_if_neq(Rooms(list));
+}
+
+fn announce_room(
+    time: Res<Time<Real>>,
+    mut timer: ResMut<AnnounceTimer>,
+    lobby_config: Res<LobbyConfig>,
+    socket: Option<Res<MatchboxSocket>>,
+    mut directory: ResMut<DirectorySocket>,
+) {
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // Private rooms are only reachable by code, so never list them
+    let Some(socket) = socket.filter(|_| lobby_config.code.is_empty()) else {
+        return;
+    };
+
+    let announcement = RoomAnnouncement {
+        room: lobby_config.room.clone(),
+        players: socket.connected_peers().count() + 1,
+        max_players: lobby_config.players,
+    };
+    let packet = serde_json::to_vec(&announcement)
+        .expect("room announcement is always serializable")
+        .into_boxed_slice();
+
+    let peers: Vec<PeerId> = directory.connected_peers().collect();
+    for peer in peers {
+        directory.channel_mut(0).send(packet.clone(), peer);
+    }
+}
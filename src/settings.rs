@@ -0,0 +1,560 @@
+//! Local, user-facing settings.
+//!
+//! Everything here only changes how the game looks or feels on this client. Anything that changes
+//! the simulation has to be identical on every peer, so it lives in
+//! [`physics_config`](crate::physics_config) instead, but is still saved alongside these in the
+//! [`Settings`] file.
+
+use std::path::PathBuf;
+
+use bevy::{audio::Volume, prelude::*, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use crate::{music::MusicDuck, physics_config::PhysicsConfig};
+
+/// Window size the UI was laid out for
+const REFERENCE_WINDOW_SIZE: f32 = 640.0;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = Settings::load();
+
+        app.insert_resource(settings.graphics)
+            .insert_resource(settings.ui)
+            .insert_resource(settings.accessibility)
+            .insert_resource(settings.audio)
+            .insert_resource(settings.hud)
+            .insert_resource(settings.controls)
+            .insert_resource(settings.camera)
+            .insert_resource(settings.network)
+            .insert_resource(settings.physics)
+            .add_message::<ResetSettings>()
+            .add_systems(
+                Update,
+                (
+                    apply_ui_scale,
+                    apply_audio_volume,
+                    (reset_settings, save_settings).chain(),
+                ),
+            );
+    }
+}
+
+/// Everything saved to the settings file. Fields missing from the file, like ones added since it
+/// was written, are filled in with their defaults, and ones we don't know about are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub graphics: GraphicsSettings,
+    pub ui: UiSettings,
+    pub accessibility: AccessibilitySettings,
+    pub audio: AudioSettings,
+    pub hud: HudSettings,
+    pub controls: ControlSettings,
+    pub camera: CameraSettings,
+    pub network: NetworkSettings,
+    pub physics: PhysicsConfig,
+}
+
+impl Settings {
+    /// Reads the settings file, falling back to defaults if there isn't one or it's unreadable
+    fn load() -> Settings {
+        let Some(path) = settings_path() else {
+            return Settings::default();
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Settings::default(),
+            Err(err) => {
+                warn!("failed to read settings from {}: {err}", path.display());
+                return Settings::default();
+            }
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(settings) => {
+                info!("loaded settings from {}", path.display());
+                settings
+            }
+            Err(err) => {
+                warn!("ignoring invalid settings in {}: {err}", path.display());
+                Settings::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = settings_path() else {
+            return;
+        };
+
+        let json = serde_json::to_string_pretty(self).expect("settings are always serializable");
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, json));
+
+        if let Err(err) = result {
+            warn!("failed to save settings to {}: {err}", path.display());
+        }
+    }
+}
+
+/// Sent to put every setting back to its default
+#[derive(Message, Clone, Copy, Debug)]
+pub struct ResetSettings;
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub round_end_trails: RoundEndTrails,
+    /// Briefly point an arrow the way each player is facing when a round starts
+    pub spawn_indicators: bool,
+    /// How far the camera shakes when you die or get a kill, 0 to turn it off
+    pub screen_shake: f32,
+    pub screen_shake_secs: f32,
+    /// How much remote players' models lag behind their simulated position to hide rollback
+    /// corrections, from 0 (off) to 0.95
+    pub remote_smoothing: f32,
+    /// Trail segments fade to translucent over this many seconds, 0 to keep them solid
+    pub trail_fade_secs: f32,
+    /// Draw where the local player is headed over the next second, for getting used to steering
+    /// on a sphere
+    pub predicted_path: bool,
+    /// Gently pulse each player's trail glow out of step with everyone else's, so crossing
+    /// trails in similar colors can still be told apart. Off with reduced motion.
+    pub trail_owner_pulse: bool,
+    /// What shows where a player dies
+    pub death_effects: DeathEffects,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            round_end_trails: RoundEndTrails::default(),
+            spawn_indicators: true,
+            screen_shake: 0.15,
+            screen_shake_secs: 0.3,
+            remote_smoothing: 0.0,
+            trail_fade_secs: 0.0,
+            predicted_path: false,
+            trail_owner_pulse: true,
+            death_effects: DeathEffects::default(),
+        }
+    }
+}
+
+/// What's left where a player dies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathEffects {
+    /// A burst of sparks in their color
+    #[default]
+    Full,
+    /// A single expanding flash, for weak hardware
+    Simple,
+    /// Nothing, they just vanish
+    Off,
+}
+
+/// What happens to the last round's trails while waiting for the next round
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundEndTrails {
+    /// Leave them as they were when the round ended
+    #[default]
+    Keep,
+    /// Leave them, but fade them out as the next round approaches
+    Fade,
+    /// Hide them as soon as the round ends
+    Hide,
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// Keep the camera from spinning with the player, and skip effects like screen shake
+    pub reduced_motion: bool,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudSettings {
+    pub show_scoreboard: bool,
+    pub scoreboard_position: ScreenPosition,
+    /// Panel with placements and who's still alive, toggled with F2
+    pub show_standings: bool,
+    /// Whether the scoreboard keeps players in place or ranks them
+    pub scoreboard_order: ScoreboardOrder,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        HudSettings {
+            show_scoreboard: true,
+            scoreboard_position: ScreenPosition::default(),
+            show_standings: false,
+            scoreboard_order: ScoreboardOrder::default(),
+        }
+    }
+}
+
+/// How the scores along the top are ordered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoreboardOrder {
+    /// Player 1 first, always in the same place
+    #[default]
+    Handle,
+    /// Highest score first, ties in handle order, with each score labeled by player
+    Score,
+}
+
+/// Where along the edge of the screen a HUD element sits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScreenPosition {
+    TopLeft,
+    #[default]
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl ScreenPosition {
+    /// `(vertical, horizontal)` alignment for a node in a full screen column
+    pub fn alignment(self) -> (JustifyContent, AlignSelf) {
+        let vertical = match self {
+            ScreenPosition::TopLeft | ScreenPosition::Top | ScreenPosition::TopRight => {
+                JustifyContent::FlexStart
+            }
+            ScreenPosition::BottomLeft | ScreenPosition::Bottom | ScreenPosition::BottomRight => {
+                JustifyContent::FlexEnd
+            }
+        };
+        let horizontal = match self {
+            ScreenPosition::TopLeft | ScreenPosition::BottomLeft => AlignSelf::FlexStart,
+            ScreenPosition::Top | ScreenPosition::Bottom => AlignSelf::Center,
+            ScreenPosition::TopRight | ScreenPosition::BottomRight => AlignSelf::FlexEnd,
+        };
+        (vertical, horizontal)
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    /// Jump doubles as hover unless hover is given its own key
+    pub hover_key: HoverKey,
+    /// Rumble connected gamepads on deaths and dashes
+    pub haptics: bool,
+    /// Turn harder the further the stick is pushed, instead of always at full speed
+    pub analog_turn: bool,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        ControlSettings {
+            hover_key: HoverKey::default(),
+            haptics: true,
+            analog_turn: false,
+        }
+    }
+}
+
+/// What hovers, see [`ControlSettings::hover_key`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HoverKey {
+    /// Keep holding jump through the top of a jump, the right trigger does nothing
+    #[default]
+    Jump,
+    /// Hold left Shift, or the right trigger, any time on the way down
+    Shift,
+    /// Hold C, or the right trigger, any time on the way down
+    C,
+}
+
+impl HoverKey {
+    /// The key that hovers, `None` when it's jump's
+    pub fn key_code(self) -> Option<KeyCode> {
+        match self {
+            HoverKey::Jump => None,
+            HoverKey::Shift => Some(KeyCode::ShiftLeft),
+            HoverKey::C => Some(KeyCode::KeyC),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub mode: CameraMode,
+    /// How far above the player the follow camera sits in a square window. Narrower windows pull
+    /// it further out so the same area fits across.
+    pub follow_height: f32,
+    /// Swing the camera once around the sphere during the countdown at the start of each round,
+    /// see [`PhysicsConfig::round_countdown_frames`], to show where everyone is. F5 skips it.
+    pub round_intro: bool,
+    /// Where the [`CameraMode::Overview`] camera was last left
+    pub overview: OverviewOrbit,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            mode: CameraMode::default(),
+            follow_height: 8.0,
+            round_intro: false,
+            overview: OverviewOrbit::default(),
+        }
+    }
+}
+
+/// Where the [`CameraMode::Overview`] camera is looking at the sphere from, adjusted with IJKL
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverviewOrbit {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// From the center of the sphere
+    pub distance: f32,
+}
+
+impl Default for OverviewOrbit {
+    fn default() -> Self {
+        OverviewOrbit {
+            yaw: 0.0,
+            pitch: 0.5,
+            // Four times the sphere's radius
+            distance: 16.0,
+        }
+    }
+}
+
+impl OverviewOrbit {
+    /// Where the camera sits to look at the whole sphere from here
+    pub fn transform(&self) -> Transform {
+        let direction = Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0) * Vec3::Z;
+        Transform::from_translation(direction * self.distance).looking_at(Vec3::ZERO, Vec3::Y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CameraMode {
+    /// Follow the local player, or whoever is being spectated
+    #[default]
+    Follow,
+    /// Frame the whole sphere without following anyone, for showing a match to an audience.
+    /// J and L orbit, I and K zoom.
+    Overview,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// How many frames we'll run ahead of the last inputs we've heard from everyone. More hides
+    /// laggier peers for longer, at the cost of bigger rollbacks when their inputs arrive. Past
+    /// this the game waits for them. Only read when a match starts.
+    pub max_prediction_frames: usize,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        NetworkSettings {
+            max_prediction_frames: 12,
+        }
+    }
+}
+
+/// Volumes from 0 (muted) to 1
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+    /// Index into [`MUSIC_TRACKS`](crate::music::MUSIC_TRACKS) played in the menus
+    pub menu_track: usize,
+    /// Index into [`MUSIC_TRACKS`](crate::music::MUSIC_TRACKS) played during matches
+    pub match_track: usize,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 1.0,
+            sfx: 1.0,
+            music: 1.0,
+            menu_track: 0,
+            match_track: 1,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn volume(&self, kind: SoundKind) -> Volume {
+        let volume = match kind {
+            SoundKind::Sfx => self.sfx,
+            SoundKind::Music => self.music,
+        };
+        Volume::Linear((self.master * volume).clamp(0.0, 1.0))
+    }
+}
+
+/// Which volume applies to an entity playing audio
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundKind {
+    Sfx,
+    Music,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    /// Multiplier on top of the automatic scaling
+    pub scale: f32,
+    /// Grow and shrink the UI with the window, relative to the default 640x640 window
+    pub scale_with_window: bool,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            scale: 1.0,
+            scale_with_window: true,
+        }
+    }
+}
+
+/// Sizes in the UI are in logical pixels, so bevy already accounts for the window's DPI scale
+/// factor, this handles the window size and the user's own preference on top
+fn apply_ui_scale(
+    settings: Res<UiSettings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let window_scale = if settings.scale_with_window {
+        window.width().min(window.height()) / REFERENCE_WINDOW_SIZE
+    } else {
+        1.0
+    };
+
+    let scale = (settings.scale * window_scale).max(0.1);
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+}
+
+/// Keeps every playing sound at its [`SoundKind`]'s volume, including ones that just started
+fn apply_audio_volume(
+    settings: Res<AudioSettings>,
+    duck: Option<Res<MusicDuck>>,
+    sinks: Query<(&SoundKind, &mut AudioSink)>,
+) {
+    for (kind, mut sink) in sinks {
+        let mut volume = settings.volume(*kind);
+        if *kind == SoundKind::Music
+            && let Some(duck) = &duck
+        {
+            volume = Volume::Linear(volume.to_linear() * duck.factor());
+        }
+        if sink.volume() != volume {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reset_settings(
+    mut reset: MessageReader<ResetSettings>,
+    mut graphics: ResMut<GraphicsSettings>,
+    mut ui: ResMut<UiSettings>,
+    mut accessibility: ResMut<AccessibilitySettings>,
+    mut audio: ResMut<AudioSettings>,
+    mut hud: ResMut<HudSettings>,
+    mut controls: ResMut<ControlSettings>,
+    mut camera: ResMut<CameraSettings>,
+    mut network: ResMut<NetworkSettings>,
+    mut physics: ResMut<PhysicsConfig>,
+) {
+    if reset.read().count() == 0 {
+        return;
+    }
+
+    info!("resetting settings to defaults");
+    graphics.set_if_neq(default());
+    ui.set_if_neq(default());
+    accessibility.set_if_neq(default());
+    audio.set_if_neq(default());
+    hud.set_if_neq(default());
+    controls.set_if_neq(default());
+    camera.set_if_neq(default());
+    network.set_if_neq(default());
+    physics.set_if_neq(default());
+}
+
+/// Writes the settings file whenever any setting changes
+#[allow(clippy::too_many_arguments)]
+fn save_settings(
+    graphics: Res<GraphicsSettings>,
+    ui: Res<UiSettings>,
+    accessibility: Res<AccessibilitySettings>,
+    audio: Res<AudioSettings>,
+    hud: Res<HudSettings>,
+    controls: Res<ControlSettings>,
+    camera: Res<CameraSettings>,
+    network: Res<NetworkSettings>,
+    physics: Res<PhysicsConfig>,
+    mut loaded: Local<bool>,
+) {
+    // Everything counts as changed on the first run, and we just loaded it
+    if !*loaded {
+        *loaded = true;
+        return;
+    }
+
+    if !(graphics.is_changed()
+        || ui.is_changed()
+        || accessibility.is_changed()
+        || audio.is_changed()
+        || hud.is_changed()
+        || controls.is_changed()
+        || camera.is_changed()
+        || network.is_changed()
+        || physics.is_changed())
+    {
+        return;
+    }
+
+    Settings {
+        graphics: graphics.clone(),
+        ui: ui.clone(),
+        accessibility: accessibility.clone(),
+        audio: audio.clone(),
+        hud: hud.clone(),
+        controls: controls.clone(),
+        camera: camera.clone(),
+        network: network.clone(),
+        physics: physics.clone(),
+    }
+    .save();
+}
+
+/// Where the settings file lives, `None` on the web where there's no file system
+fn settings_path() -> Option<PathBuf> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+
+    let env_path = |var| std::env::var_os(var).map(PathBuf::from);
+    let config_dir = if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    };
+
+    Some(config_dir?.join("galaxy-cats").join("settings.json"))
+}